@@ -0,0 +1,215 @@
+//! A small reduced ordered binary decision diagram (ROBDD) package.
+//!
+//! All functions live in one [Bdd] manager, which hash-conses the nodes: two
+//! functions are equal if and only if their [BddRef]s are equal. Variables are
+//! ordered by number, with the lowest number at the root.
+
+use hashbrown::HashMap;
+
+/// Handle to a function stored in a [Bdd]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BddRef(u32);
+
+impl BddRef {
+    pub const FALSE: Self = Self(0);
+    pub const TRUE: Self = Self(1);
+
+    pub fn is_const(self) -> bool {
+        self == Self::FALSE || self == Self::TRUE
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinOp {
+    And,
+    Or,
+    Xor,
+}
+
+impl BinOp {
+    fn apply_const(self, a: bool, b: bool) -> bool {
+        match self {
+            BinOp::And => a && b,
+            BinOp::Or => a || b,
+            BinOp::Xor => a ^ b,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct BddNode {
+    var: u32,
+    low: BddRef,
+    high: BddRef,
+}
+
+#[derive(Debug, Clone)]
+pub struct Bdd {
+    nodes: Vec<BddNode>,
+    unique: HashMap<BddNode, BddRef>,
+    apply_cache: HashMap<(BinOp, BddRef, BddRef), BddRef>,
+}
+
+impl Default for Bdd {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bdd {
+    pub fn new() -> Self {
+        // the terminals sit below every variable
+        let terminal = |value| BddNode {
+            var: u32::MAX,
+            low: value,
+            high: value,
+        };
+        Self {
+            nodes: vec![terminal(BddRef::FALSE), terminal(BddRef::TRUE)],
+            unique: HashMap::new(),
+            apply_cache: HashMap::new(),
+        }
+    }
+
+    pub fn constant(&self, value: bool) -> BddRef {
+        if value {
+            BddRef::TRUE
+        } else {
+            BddRef::FALSE
+        }
+    }
+
+    /// The function that is true exactly when variable `var` is
+    pub fn var(&mut self, var: u32) -> BddRef {
+        self.make_node(var, BddRef::FALSE, BddRef::TRUE)
+    }
+
+    pub fn and(&mut self, a: BddRef, b: BddRef) -> BddRef {
+        self.apply(BinOp::And, a, b)
+    }
+
+    pub fn or(&mut self, a: BddRef, b: BddRef) -> BddRef {
+        self.apply(BinOp::Or, a, b)
+    }
+
+    pub fn xor(&mut self, a: BddRef, b: BddRef) -> BddRef {
+        self.apply(BinOp::Xor, a, b)
+    }
+
+    #[allow(unused)]
+    pub fn not(&mut self, a: BddRef) -> BddRef {
+        self.apply(BinOp::Xor, a, BddRef::TRUE)
+    }
+
+    fn make_node(&mut self, var: u32, low: BddRef, high: BddRef) -> BddRef {
+        if low == high {
+            return low;
+        }
+        let node = BddNode { var, low, high };
+        if let Some(&r) = self.unique.get(&node) {
+            return r;
+        }
+        let r = BddRef(self.nodes.len() as u32);
+        self.nodes.push(node);
+        self.unique.insert(node, r);
+        r
+    }
+
+    pub fn apply(&mut self, op: BinOp, a: BddRef, b: BddRef) -> BddRef {
+        if a.is_const() && b.is_const() {
+            return self.constant(op.apply_const(a == BddRef::TRUE, b == BddRef::TRUE));
+        }
+        // all three operations are commutative
+        let key = (op, a.min(b), a.max(b));
+        if let Some(&r) = self.apply_cache.get(&key) {
+            return r;
+        }
+
+        let (node_a, node_b) = (self.nodes[a.0 as usize], self.nodes[b.0 as usize]);
+        let var = node_a.var.min(node_b.var);
+        let (a_low, a_high) = if node_a.var == var {
+            (node_a.low, node_a.high)
+        } else {
+            (a, a)
+        };
+        let (b_low, b_high) = if node_b.var == var {
+            (node_b.low, node_b.high)
+        } else {
+            (b, b)
+        };
+        let low = self.apply(op, a_low, b_low);
+        let high = self.apply(op, a_high, b_high);
+        let r = self.make_node(var, low, high);
+
+        self.apply_cache.insert(key, r);
+        r
+    }
+
+    /// Evaluate the function for the given variable assignment
+    #[allow(unused)]
+    pub fn eval(&self, f: BddRef, assignment: impl Fn(u32) -> bool) -> bool {
+        let mut r = f;
+        while !r.is_const() {
+            let node = self.nodes[r.0 as usize];
+            r = if assignment(node.var) {
+                node.high
+            } else {
+                node.low
+            };
+        }
+        r == BddRef::TRUE
+    }
+
+    /// Some assignment that makes the function true, as a list of the
+    /// variables that matter and their values; `None` if there is none
+    pub fn satisfying_assignment(&self, f: BddRef) -> Option<Vec<(u32, bool)>> {
+        if f == BddRef::FALSE {
+            return None;
+        }
+        let mut assignment = vec![];
+        let mut r = f;
+        // every non-terminal node has a path to TRUE, as FALSE-only nodes
+        // would have been reduced to FALSE
+        while !r.is_const() {
+            let node = self.nodes[r.0 as usize];
+            if node.low != BddRef::FALSE {
+                assignment.push((node.var, false));
+                r = node.low;
+            } else {
+                assignment.push((node.var, true));
+                r = node.high;
+            }
+        }
+        Some(assignment)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bdd_test() {
+        let mut bdd = Bdd::new();
+        let (a, b, c) = (bdd.var(0), bdd.var(1), bdd.var(2));
+
+        // De Morgan: !(a & b) == !a | !b
+        let ab = bdd.and(a, b);
+        let lhs = bdd.not(ab);
+        let (not_a, not_b) = (bdd.not(a), bdd.not(b));
+        let rhs = bdd.or(not_a, not_b);
+        assert_eq!(lhs, rhs);
+
+        let a_xor_a = bdd.xor(a, a);
+        assert_eq!(a_xor_a, BddRef::FALSE);
+
+        // a & !b & c
+        let f = bdd.and(a, not_b);
+        let f = bdd.and(f, c);
+        let assignment = bdd.satisfying_assignment(f).unwrap();
+        assert_eq!(assignment, [(0, true), (1, false), (2, true)]);
+        assert!(bdd.eval(f, |var| var != 1));
+        assert!(!bdd.eval(f, |_| true));
+        assert_eq!(bdd.satisfying_assignment(BddRef::FALSE), None);
+    }
+}
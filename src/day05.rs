@@ -156,7 +156,7 @@ fn reorder_list(page_list: &mut [i32], rules: &[Rule]) {
 mod test {
     use super::*;
 
-    static TEST_INPUT: &'static str = "\
+    static TEST_INPUT: &str = "\
         47|53\n\
         97|13\n\
         97|61\n\
@@ -194,7 +194,7 @@ fn has_loop(map: &Map, mut pos: Pos, mut dir: Direction) -> bool {
 mod test {
     use super::*;
 
-    static TEST_INPUT: &'static str = "\
+    static TEST_INPUT: &str = "\
         ....#.....\n\
         .........#\n\
         ..........\n\
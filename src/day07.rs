@@ -120,7 +120,7 @@ fn parse_rules(lines: &[&str]) -> Vec<BareEquation> {
 mod test {
     use super::*;
 
-    static TEST_INPUT: &'static str = "\
+    static TEST_INPUT: &str = "\
         190: 10 19\n\
         3267: 81 40 27\n\
         83: 17 5\n\
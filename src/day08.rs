@@ -155,7 +155,7 @@ fn find_antinodes2(size: Size, towers: &[Point]) -> HashSet<Point> {
 mod test {
     use super::*;
 
-    static TEST_INPUT: &'static str = "\
+    static TEST_INPUT: &str = "\
         ............\n\
         ........0...\n\
         .....0......\n\
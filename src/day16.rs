@@ -1,4 +1,10 @@
-use std::{cmp::Ordering, collections::BinaryHeap, fmt::Debug, ops::Add, str::FromStr};
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fmt::{Debug, Display},
+    ops::Add,
+    str::FromStr,
+};
 
 use hashbrown::{HashMap, HashSet};
 use itertools::Itertools;
@@ -6,20 +12,145 @@ use itertools::Itertools;
 pub fn day16(input: &str) -> (i32, usize) {
     let map: Map<Tile> = input.parse().unwrap();
 
-    let (score, paths) = get_best_paths(&map);
+    solve(&map, &CostModel::default())
+}
+
+/// The best score and the number of tiles on best paths, with a different
+/// cost model: `--step N` (default 1), `--turn N` (default 1000),
+/// `--u-turns` to allow turning around on the spot, and `--weights FILE` for
+/// a grid of digits, the same size as the maze, to multiply the step cost by.
+/// With `--paths N`, also draw the first N best paths.
+pub fn day16costs(input: &str, args: &[&str]) -> String {
+    let map: Map<Tile> = input.parse().unwrap();
+
+    let mut costs = CostModel::default();
+    let mut n_paths = 0;
+    let mut args = args.iter();
+    while let Some(&option) = args.next() {
+        let mut value = || *args.next().expect("missing value");
+        match option {
+            "--step" => costs.step = value().parse().unwrap(),
+            "--turn" => costs.turn = value().parse().unwrap(),
+            "--u-turns" => costs.u_turns = true,
+            "--paths" => n_paths = value().parse().unwrap(),
+            "--weights" => {
+                let weights = std::fs::read_to_string(value()).unwrap();
+                costs.weights = Some(weights.parse().unwrap());
+            }
+            _ => panic!("unknown option {option}"),
+        }
+    }
+
+    let best_paths = find_best_paths(&map, &costs);
+    let Some(score) = best_paths.score else {
+        return "the end cannot be reached\n".to_owned();
+    };
+    let mut out = format!(
+        "best score: {score}\ntiles on best paths: {}\n",
+        best_paths.tiles().len()
+    );
+    for path in best_paths.paths().take(n_paths) {
+        out += "\n";
+        out += &render(&map, &path);
+    }
+    out
+}
+
+/// The best score (or -1 if the end cannot be reached) and the number of
+/// tiles on best paths
+fn solve(map: &Map<Tile>, costs: &CostModel) -> (i32, usize) {
+    let best_paths = find_best_paths(map, costs);
+
+    (best_paths.score.unwrap_or(-1), best_paths.tiles().len())
+}
+
+/// The maze with `O` along a path
+fn render(map: &Map<Tile>, path: &[Pos]) -> String {
+    let path: HashSet<Pos> = path.iter().copied().collect();
+    let mut out = String::new();
+    for y in 0..map.height() {
+        for x in 0..map.width() {
+            let tile = *map.get(Pos(x, y)).unwrap();
+            if tile == Tile::Path && path.contains(&Pos(x, y)) {
+                out.push('O');
+            } else {
+                out += &tile.to_string();
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// What moving through the maze costs
+#[derive(Debug, Clone)]
+struct CostModel {
+    /// Stepping forward onto a tile (of weight 1)
+    pub step: i32,
+    /// Turning 90 degrees; a turn is always followed by a step
+    pub turn: i32,
+    /// Whether turning around (for two turns) is allowed, rather than only
+    /// left and right
+    pub u_turns: bool,
+    /// How many steps stepping onto each tile counts as; 1 everywhere if not
+    /// given
+    pub weights: Option<Map<i32>>,
+}
 
-    let mut best_spots = HashSet::new();
-    for path in paths {
-        best_spots.extend(path);
+impl Default for CostModel {
+    fn default() -> Self {
+        Self {
+            step: 1,
+            turn: 1000,
+            u_turns: false,
+            weights: None,
+        }
     }
+}
 
-    (score, best_spots.len())
+impl CostModel {
+    /// The cost of stepping onto `pos`
+    pub fn step_onto(&self, pos: Pos) -> i32 {
+        match &self.weights {
+            Some(weights) => self.step * weights.get(pos).copied().unwrap_or(1),
+            None => self.step,
+        }
+    }
+
+    /// The cost of stepping onto the lightest tile
+    pub fn cheapest_step(&self) -> i32 {
+        match &self.weights {
+            Some(weights) => self.step * weights.matrix.iter().copied().min().unwrap_or(1),
+            None => self.step,
+        }
+    }
+
+    /// A lower bound on the cost of getting from `pos`, facing `dir`, to
+    /// `target`: the cheapest steps (see [CostModel::cheapest_step]), and the
+    /// turns needed to face every way the target lies
+    pub fn estimate(&self, pos: Pos, dir: Direction, target: Pos, cheapest_step: i32) -> i32 {
+        let (dx, dy) = (target.0 - pos.0, target.1 - pos.1);
+        let ways = [
+            (dx > 0, Direction::East),
+            (dx < 0, Direction::West),
+            (dy > 0, Direction::South),
+            (dy < 0, Direction::North),
+        ];
+        let turns = if ways.contains(&(true, dir.turn_right().turn_right())) {
+            2
+        } else if ways.iter().any(|&(needed, way)| needed && way != dir) {
+            1
+        } else {
+            0
+        };
+        (dx.abs() + dy.abs()) * cheapest_step + turns * self.turn
+    }
 }
 
 #[allow(unused)]
 fn draw_path(path: &[Pos]) {
     let max_x = path.iter().map(|p| p.0).max().unwrap();
-    let max_y = path.iter().map(|p| p.0).max().unwrap();
+    let max_y = path.iter().map(|p| p.1).max().unwrap();
 
     let mut bitmap = Vec::new();
     for _y in 0..=max_y {
@@ -42,77 +173,172 @@ fn draw_path(path: &[Pos]) {
     );
 }
 
-fn get_best_paths(map: &Map<Tile>) -> (i32, Vec<Vec<Pos>>) {
-    let start_pos = map.find_all(|&tile| tile == Tile::Start).next().unwrap();
-    let end_pos = map.find_all(|&tile| tile == Tile::End).next().unwrap();
+/// Where the reindeer is and which way it is facing
+type State = (Pos, Direction);
 
-    let distance = |pos: Pos| {
-        let x_dist = (pos.0 - end_pos.0).abs();
-        let y_dist = (pos.0 - end_pos.0).abs();
-        let turn_penalty = if x_dist != 0 && y_dist != 0 { 1000 } else { 0 };
-        x_dist + y_dist + turn_penalty
-    };
+/// What the search found out about best paths: rather than the paths
+/// themselves, the states at the end of them, and for every state reached,
+/// all the states it can be reached from as cheaply as possible
+#[derive(Debug, Clone)]
+struct BestPaths {
+    pub score: Option<i32>,
+    start: State,
+    ends: Vec<State>,
+    predecessors: HashMap<State, Vec<State>>,
+}
 
-    let mut queue = BinaryHeap::new();
-    queue.push((-distance(start_pos), 0, vec![start_pos], Direction::East));
+impl BestPaths {
+    /// Every tile on some best path, found by walking back from the end
+    pub fn tiles(&self) -> HashSet<Pos> {
+        let mut seen: HashSet<State> = self.ends.iter().copied().collect();
+        let mut stack = self.ends.clone();
+        while let Some(state) = stack.pop() {
+            for &prev in self.predecessors.get(&state).into_iter().flatten() {
+                if seen.insert(prev) {
+                    stack.push(prev);
+                }
+            }
+        }
+        seen.into_iter().map(|(pos, _)| pos).collect()
+    }
 
-    let mut min_score = HashMap::new();
+    /// The best paths themselves, one at a time, from start to end
+    pub fn paths(&self) -> BestPathIter<'_> {
+        BestPathIter {
+            best_paths: self,
+            ends: self.ends.iter(),
+            frames: vec![],
+        }
+    }
+}
 
-    let mut paths = vec![];
-    let mut score = None;
+/// Lazily walks back from the end along every chain of predecessors
+struct BestPathIter<'a> {
+    best_paths: &'a BestPaths,
+    /// End states still to start from
+    ends: std::slice::Iter<'a, State>,
+    /// Depth-first search state: the states on the current chain, from the
+    /// end backwards, each with the next of its predecessors to try
+    frames: Vec<(State, usize)>,
+}
 
-    while let Some((_dist, balance, mut path, dir)) = queue.pop() {
-        let pos = *path.last().unwrap();
-        let min_score_here = min_score.entry((pos, dir)).or_insert(-balance);
-        match (*min_score_here).cmp(&(-balance)) {
-            Ordering::Less => continue,
-            _ => *min_score_here = -balance,
+impl Iterator for BestPathIter<'_> {
+    type Item = Vec<Pos>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.frames.is_empty() {
+                self.frames.push((*self.ends.next()?, 0));
+            }
+            let (state, next_pred) = *self.frames.last().unwrap();
+            let preds = self
+                .best_paths
+                .predecessors
+                .get(&state)
+                .map_or(&[][..], Vec::as_slice);
+
+            if state == self.best_paths.start {
+                // with free moves the start can have predecessors too, but
+                // a path ends here all the same
+                let path = self.frames.iter().rev().map(|&((pos, _), _)| pos).collect();
+                self.frames.pop();
+                return Some(path);
+            } else if let Some(&prev) = preds.get(next_pred) {
+                self.frames.last_mut().unwrap().1 += 1;
+                // with free moves there can be loops; don't go round them
+                if !self.frames.iter().any(|&(s, _)| s == prev) {
+                    self.frames.push((prev, 0));
+                }
+            } else {
+                self.frames.pop();
+            }
         }
+    }
+}
 
-        if score.is_some_and(|s| -balance > s) {
+/// A* search that keeps only the best cost to each state and where it came
+/// from, rather than whole paths
+fn find_best_paths(map: &Map<Tile>, costs: &CostModel) -> BestPaths {
+    let start_pos = map.find_all(|&tile| tile == Tile::Start).next().unwrap();
+    let end_pos = map.find_all(|&tile| tile == Tile::End).next().unwrap();
+    assert!(costs.step >= 0, "negative step cost");
+    assert!(costs.turn >= 0, "negative turn cost");
+    if let Some(weights) = &costs.weights {
+        assert!(weights.matrix.iter().all(|&w| w >= 0), "negative weight");
+    }
+
+    let cheapest_step = costs.cheapest_step();
+    let distance = |pos: Pos, dir: Direction| costs.estimate(pos, dir, end_pos, cheapest_step);
+
+    let start = (start_pos, Direction::East);
+    let mut best_cost: HashMap<State, i32> = HashMap::from([(start, 0)]);
+    let mut predecessors: HashMap<State, Vec<State>> = HashMap::new();
+    let mut queue = BinaryHeap::from([Reverse((distance(start_pos, Direction::East), 0, start))]);
+
+    let mut score = None;
+    let mut ends = vec![];
+
+    while let Some(Reverse((estimate, cost, state))) = queue.pop() {
+        // the estimate never overshoots, so nothing from here on can be on a
+        // best path
+        if score.is_some_and(|s| estimate > s) {
             break;
         }
+        if cost > best_cost[&state] {
+            // there's a cheaper way here
+            continue;
+        }
 
-        if map.get(pos).is_some_and(|&t| t == Tile::End) {
+        let (pos, dir) = state;
+        if pos == end_pos {
             // found a path to the end
-            paths.push(path);
-            score = Some(-balance);
+            score = Some(cost);
+            ends.push(state);
             continue;
         }
         // survey the options!
-        let a_droite = pos + dir.turn_right();
-        if map.get(a_droite).is_some_and(|&t| t != Tile::Wall) {
+        let options = [
+            // can go straight
+            (dir, 0),
             // going right might be nice
-            let mut new_path = path.clone();
-            new_path.push(a_droite);
-            queue.push((
-                -distance(a_droite) + balance - 1001,
-                balance - 1001,
-                new_path,
-                dir.turn_right(),
-            ));
-        }
-        let a_gauche = pos + dir.turn_left();
-        if map.get(a_gauche).is_some_and(|&t| t != Tile::Wall) {
+            (dir.turn_right(), costs.turn),
             // im Zweifel links
-            let mut new_path = path.clone();
-            new_path.push(a_gauche);
-            queue.push((
-                -distance(a_gauche) + balance - 1001,
-                balance - 1001,
-                new_path,
-                dir.turn_left(),
-            ));
-        }
-        let straight = pos + dir;
-        if map.get(straight).is_some_and(|&t| t != Tile::Wall) {
-            // can go straight
-            path.push(straight);
-            queue.push((-distance(straight) + balance - 1, balance - 1, path, dir));
+            (dir.turn_left(), costs.turn),
+            // or all the way round, if that's allowed
+            (dir.turn_right().turn_right(), 2 * costs.turn),
+        ];
+        let n_options = if costs.u_turns { 4 } else { 3 };
+        for (new_dir, turn_cost) in options.into_iter().take(n_options) {
+            let next = pos + new_dir;
+            if !map.get(next).is_some_and(|&t| t != Tile::Wall) {
+                continue;
+            }
+            let next_state = (next, new_dir);
+            let next_cost = cost + turn_cost + costs.step_onto(next);
+            match best_cost.get(&next_state) {
+                Some(&c) if c < next_cost => {}
+                Some(&c) if c == next_cost => {
+                    predecessors.entry(next_state).or_default().push(state)
+                }
+                _ => {
+                    best_cost.insert(next_state, next_cost);
+                    predecessors.insert(next_state, vec![state]);
+                    queue.push(Reverse((
+                        next_cost + distance(next, new_dir),
+                        next_cost,
+                        next_state,
+                    )));
+                }
+            }
         }
     }
 
-    (score.unwrap_or(-1), paths)
+    BestPaths {
+        score,
+        start,
+        ends,
+        predecessors,
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -224,6 +450,20 @@ enum Tile {
     End,
 }
 
+impl Display for Tile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(
+            &match self {
+                Self::Start => 'S',
+                Self::Path => '.',
+                Self::Wall => '#',
+                Self::End => 'E',
+            },
+            f,
+        )
+    }
+}
+
 impl FromStr for Tile {
     type Err = ();
 
@@ -291,4 +531,111 @@ mod test {
         assert_eq!(day16(TEST_INPUT_1).1, 45);
         assert_eq!(day16(TEST_INPUT_2).1, 64);
     }
+
+    #[test]
+    fn best_paths_test() {
+        for (input, n_paths) in [(TEST_INPUT_1, 3), (TEST_INPUT_2, 2)] {
+            let map: Map<Tile> = input.parse().unwrap();
+            let best_paths = find_best_paths(&map, &CostModel::default());
+            let paths = best_paths.paths().collect_vec();
+            assert_eq!(paths.len(), n_paths);
+
+            let start = map.find_all(|&t| t == Tile::Start).next().unwrap();
+            let end = map.find_all(|&t| t == Tile::End).next().unwrap();
+            let mut tiles = HashSet::new();
+            for path in &paths {
+                assert_eq!((path[0], *path.last().unwrap()), (start, end));
+                assert!(path
+                    .iter()
+                    .tuple_windows()
+                    .all(|(a, b)| (a.0 - b.0).abs() + (a.1 - b.1).abs() == 1));
+                tiles.extend(path.iter().copied());
+            }
+            assert_eq!(tiles, best_paths.tiles());
+        }
+    }
+
+    #[test]
+    fn cost_model_test() {
+        let map: Map<Tile> = TEST_INPUT_1.parse().unwrap();
+        let free_turns = CostModel {
+            turn: 0,
+            ..Default::default()
+        };
+        assert_eq!(solve(&map, &free_turns).0, 28);
+        let cheap_turns = CostModel {
+            turn: 500,
+            ..Default::default()
+        };
+        assert_eq!(solve(&map, &cheap_turns).0, 3536);
+
+        // the end is behind the start
+        let map: Map<Tile> = "#####\n#E.S#\n#####\n".parse().unwrap();
+        assert_eq!(solve(&map, &CostModel::default()).0, -1);
+        let u_turns = CostModel {
+            u_turns: true,
+            ..Default::default()
+        };
+        assert_eq!(solve(&map, &u_turns), (2002, 3));
+
+        // the short way round is slow going
+        let map: Map<Tile> = "\
+            #######\n\
+            #S...E#\n\
+            #.###.#\n\
+            #.....#\n\
+            #######\n\
+        "
+        .parse()
+        .unwrap();
+        let weights = "\
+            1111111\n\
+            1191111\n\
+            1111111\n\
+            1111111\n\
+            1111111\n\
+        ";
+        let costs = CostModel {
+            turn: 1,
+            weights: Some(weights.parse().unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(
+            solve(
+                &map,
+                &CostModel {
+                    turn: 1,
+                    ..Default::default()
+                }
+            ),
+            (4, 5)
+        );
+        assert_eq!(solve(&map, &costs), (11, 9));
+    }
+
+    #[test]
+    fn free_moves_test() {
+        // every tile costs nothing to reach, the start included
+        let map: Map<Tile> = "######\n#.S..#\n#....#\n#...E#\n######\n".parse().unwrap();
+        let free = CostModel {
+            step: 0,
+            turn: 0,
+            ..Default::default()
+        };
+        let weightless = CostModel {
+            turn: 0,
+            weights: Some("000000\n000000\n000000\n000000\n000000\n".parse().unwrap()),
+            ..Default::default()
+        };
+        for costs in [free, weightless] {
+            let best_paths = find_best_paths(&map, &costs);
+            assert_eq!(best_paths.score, Some(0));
+            assert_eq!(best_paths.tiles().len(), 12);
+            let paths = best_paths.paths().take(100).collect_vec();
+            assert!(!paths.is_empty());
+            for path in paths {
+                assert_eq!((path[0], *path.last().unwrap()), (Pos(2, 1), Pos(4, 3)));
+            }
+        }
+    }
 }
@@ -1,14 +1,19 @@
+use hashbrown::HashSet;
 use itertools::Itertools;
 
+/// Generous upper bound on the number of instructions a puzzle program may run
+const DEFAULT_STEP_BUDGET: usize = 10_000_000;
+
 pub fn day17part1(input: &str) -> String {
     let problem = Problem::new_from_input(input).unwrap();
     let computer = Computer::new(&problem);
-    let output = computer.run().unwrap();
+    let output = computer.run(Some(DEFAULT_STEP_BUDGET)).unwrap();
     output.into_iter().map(|i| i.to_string()).join(",")
 }
 
 pub fn day17part2(input: &str) -> i64 {
     let problem = Problem::new_from_input(input).unwrap();
+    let compiled = CompiledProgram::compile(&problem).with_step_budget(DEFAULT_STEP_BUDGET);
 
     let mut a = 0;
 
@@ -21,19 +26,14 @@ pub fn day17part2(input: &str) -> i64 {
         let required_output = &problem.program[problem.program.len() - tail_len..];
 
         let mut delta_a = 0;
-        loop {
-            let mut computer = Computer::new(&problem);
-            computer.a = a + delta_a;
-            let output = computer.run().unwrap();
-            if output == required_output {
-                a += delta_a;
-                break;
-            } else if output.len() > required_output.len() {
+        while !compiled.outputs(a + delta_a, required_output).unwrap() {
+            delta_a += 1;
+            // any more bits, and there would be more output than required
+            if (a + delta_a) >> (3 * tail_len) != 0 {
                 panic!("program does not behave as expected");
             }
-
-            delta_a += 1;
         }
+        a += delta_a;
     }
 
     a
@@ -88,11 +88,27 @@ struct Computer<'a> {
     program: &'a [u8],
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum TerminationReason {
     EndOfProgram,
     InvalidOpcode,
     InvalidProgram,
+    /// The machine returned to a state (ip, A, B, C) it had already been in
+    InfiniteLoop,
+    /// The step budget ran out before the program ended
+    BudgetExhausted,
+}
+
+/// Number of steps a [CompiledProgram] runs before it starts looking for loops
+const LOOP_CHECK_AFTER: usize = 1000;
+
+/// `value >> amount`, except that shifting by 64 or more (or by a negative
+/// amount) is defined to give 0 instead of overflowing
+fn shr(value: i64, amount: i64) -> i64 {
+    u32::try_from(amount)
+        .ok()
+        .and_then(|amount| value.checked_shr(amount))
+        .unwrap_or(0)
 }
 
 impl From<InvalidOpcode> for TerminationReason {
@@ -184,7 +200,7 @@ impl<'a> Computer<'a> {
 
         match opcode {
             Opcode::Adv => {
-                self.a >>= self.decode_combo(operand)?;
+                self.a = shr(self.a, self.decode_combo(operand)?);
             }
             Opcode::Bxl => self.b ^= self.decode_literal(operand)?,
             Opcode::Bst => {
@@ -202,25 +218,214 @@ impl<'a> Computer<'a> {
                 output.push((self.decode_combo(operand)? & 0b111) as u8);
             }
             Opcode::Bdv => {
-                self.b = self.a >> self.decode_combo(operand)?;
+                self.b = shr(self.a, self.decode_combo(operand)?);
             }
             Opcode::Cdv => {
-                self.c = self.a >> self.decode_combo(operand)?;
+                self.c = shr(self.a, self.decode_combo(operand)?);
             }
         }
 
         Ok(())
     }
 
-    pub fn run(mut self) -> Result<Vec<u8>, TerminationReason> {
+    /// Run until the program ends, executing at most `max_steps` instructions
+    /// (if given). Any loop that never terminates has to jump backwards, so
+    /// the state is recorded after every taken jump to spot repeats.
+    pub fn run(mut self, max_steps: Option<usize>) -> Result<Vec<u8>, TerminationReason> {
         let mut output = vec![];
+        let mut seen = HashSet::new();
+        let mut steps = 0;
         loop {
+            // running off the end (or into half an instruction) isn't a step
+            if self.ip + 1 >= self.program.len() {
+                return Ok(output);
+            }
+            if max_steps.is_some_and(|max| steps >= max) {
+                return Err(TerminationReason::BudgetExhausted);
+            }
+            let ip = self.ip;
             match self.step(&mut output) {
                 Ok(()) => (),
                 Err(TerminationReason::EndOfProgram) => return Ok(output),
                 Err(e) => return Err(e),
             }
+            steps += 1;
+            if self.ip != ip + 2 && !seen.insert((self.ip, self.a, self.b, self.c)) {
+                return Err(TerminationReason::InfiniteLoop);
+            }
+        }
+    }
+}
+
+/// Operand of a combo instruction, resolved at compile time
+#[derive(Debug, Clone, Copy)]
+enum Combo {
+    Literal(i64),
+    A,
+    B,
+    C,
+}
+
+/// An instruction with its operand already decoded
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Adv(Combo),
+    Bxl(i64),
+    Bst(Combo),
+    Jnz(usize),
+    Bxc,
+    Out(Combo),
+    Bdv(Combo),
+    Cdv(Combo),
+    /// Only a lone opcode with no operand left: executing this ends the program
+    Truncated,
+    /// Decoding failed; only an error if this instruction is actually reached
+    Invalid(TerminationReason),
+}
+
+/// A program lowered once into pre-decoded operations, so that running it for
+/// many different values of A doesn't repeat the decoding work.
+///
+/// There is one op for every address (not just the even ones) so that jumps to
+/// odd addresses behave exactly as they do in [Computer].
+#[derive(Debug, Clone)]
+struct CompiledProgram {
+    ops: Vec<Op>,
+    b: i64,
+    c: i64,
+    max_steps: Option<usize>,
+}
+
+impl Combo {
+    fn decode(value: u8) -> Result<Self, TerminationReason> {
+        match value {
+            0..=3 => Ok(Self::Literal(value as i64)),
+            4 => Ok(Self::A),
+            5 => Ok(Self::B),
+            6 => Ok(Self::C),
+            _ => Err(TerminationReason::InvalidProgram),
+        }
+    }
+
+    #[inline]
+    fn get(self, a: i64, b: i64, c: i64) -> i64 {
+        match self {
+            Self::Literal(v) => v,
+            Self::A => a,
+            Self::B => b,
+            Self::C => c,
+        }
+    }
+}
+
+impl Op {
+    fn decode(opcode: u8, operand: Option<u8>) -> Self {
+        // like the interpreter, don't look at an opcode without an operand
+        let Some(operand) = operand else {
+            return Self::Truncated;
+        };
+        let Ok(opcode) = Opcode::try_from(opcode) else {
+            return Self::Invalid(TerminationReason::InvalidOpcode);
+        };
+        let combo = Combo::decode(operand);
+        let op = match opcode {
+            Opcode::Adv => combo.map(Self::Adv),
+            Opcode::Bxl => Ok(Self::Bxl(operand as i64)),
+            Opcode::Bst => combo.map(Self::Bst),
+            Opcode::Jnz => Ok(Self::Jnz(operand as usize)),
+            Opcode::Bxc => Ok(Self::Bxc),
+            Opcode::Out => combo.map(Self::Out),
+            Opcode::Bdv => combo.map(Self::Bdv),
+            Opcode::Cdv => combo.map(Self::Cdv),
+        };
+        op.unwrap_or_else(Self::Invalid)
+    }
+}
+
+impl CompiledProgram {
+    pub fn compile(problem: &Problem) -> Self {
+        let program = &problem.program;
+        let ops = (0..program.len())
+            .map(|ip| Op::decode(program[ip], program.get(ip + 1).copied()))
+            .collect();
+        Self {
+            ops,
+            b: problem.b,
+            c: problem.c,
+            max_steps: None,
+        }
+    }
+
+    pub fn with_step_budget(self, max_steps: usize) -> Self {
+        Self {
+            max_steps: Some(max_steps),
+            ..self
+        }
+    }
+
+    /// Whether the program outputs exactly `expected` when started with the
+    /// given value of A. This stops as soon as the output goes wrong, which
+    /// for most values of A is at the first output.
+    pub fn outputs(&self, a: i64, expected: &[u8]) -> Result<bool, TerminationReason> {
+        let mut len = 0;
+        let finished = self.run_with(a, |value| {
+            len += 1;
+            expected.get(len - 1) == Some(&value)
+        })?;
+        Ok(finished && len == expected.len())
+    }
+
+    /// Run the program with the given initial value of A, passing each output
+    /// to `out`, which can stop the program by returning false. Returns
+    /// whether the program ran to the end.
+    ///
+    /// Loop detection only kicks in after [LOOP_CHECK_AFTER] steps, so that
+    /// the usual short runs don't pay for hashing every jump.
+    pub fn run_with(
+        &self,
+        mut a: i64,
+        mut out: impl FnMut(u8) -> bool,
+    ) -> Result<bool, TerminationReason> {
+        let (mut b, mut c) = (self.b, self.c);
+        let mut ip = 0;
+        let mut seen = HashSet::new();
+        let mut steps = 0;
+
+        while let Some(&op) = self.ops.get(ip) {
+            if let Op::Truncated = op {
+                break;
+            }
+            if self.max_steps.is_some_and(|max| steps >= max) {
+                return Err(TerminationReason::BudgetExhausted);
+            }
+            steps += 1;
+            ip += 2;
+            match op {
+                Op::Adv(x) => a = shr(a, x.get(a, b, c)),
+                Op::Bxl(x) => b ^= x,
+                Op::Bst(x) => b = x.get(a, b, c) & 0b111,
+                Op::Jnz(target) => {
+                    if a != 0 {
+                        ip = target;
+                        if steps > LOOP_CHECK_AFTER && !seen.insert((ip, a, b, c)) {
+                            return Err(TerminationReason::InfiniteLoop);
+                        }
+                    }
+                }
+                Op::Bxc => b ^= c,
+                Op::Out(x) => {
+                    if !out((x.get(a, b, c) & 0b111) as u8) {
+                        return Ok(false);
+                    }
+                }
+                Op::Bdv(x) => b = shr(a, x.get(a, b, c)),
+                Op::Cdv(x) => c = shr(a, x.get(a, b, c)),
+                Op::Truncated => unreachable!(),
+                Op::Invalid(reason) => return Err(reason),
+            }
         }
+
+        Ok(true)
     }
 }
 
@@ -228,6 +433,15 @@ impl<'a> Computer<'a> {
 mod test {
     use super::*;
 
+    fn run_compiled(compiled: &CompiledProgram, a: i64) -> Result<Vec<u8>, TerminationReason> {
+        let mut output = vec![];
+        compiled.run_with(a, |value| {
+            output.push(value);
+            true
+        })?;
+        Ok(output)
+    }
+
     static TEST_INPUT_1: &str = "\
         Register A: 729\n\
         Register B: 0\n\
@@ -253,4 +467,70 @@ mod test {
     fn part2test() {
         assert_eq!(day17part2(TEST_INPUT_2), 117440);
     }
+
+    #[test]
+    fn runaway_programs_terminate() {
+        let mut problem = Problem::new_from_input(TEST_INPUT_1).unwrap();
+
+        // bxl 1; jnz 0 -- A never changes
+        problem.program = vec![1, 1, 3, 0];
+        let computer = Computer::new(&problem);
+        assert_eq!(computer.run(None), Err(TerminationReason::InfiniteLoop));
+        let compiled = CompiledProgram::compile(&problem);
+        assert_eq!(
+            run_compiled(&compiled, 1),
+            Err(TerminationReason::InfiniteLoop)
+        );
+
+        // adv 1; out 4; jnz 0 -- terminates, but only after ~10 loops
+        problem.program = vec![0, 1, 5, 4, 3, 0];
+        let computer = Computer::new(&problem);
+        assert_eq!(
+            computer.run(Some(5)),
+            Err(TerminationReason::BudgetExhausted)
+        );
+        let compiled = CompiledProgram::compile(&problem).with_step_budget(5);
+        assert_eq!(
+            run_compiled(&compiled, 729),
+            Err(TerminationReason::BudgetExhausted)
+        );
+
+        // out 4; bxl 1 -- ends right as the budget runs out
+        problem.a = 5;
+        problem.program = vec![5, 4, 1, 1];
+        let computer = Computer::new(&problem);
+        assert_eq!(computer.run(Some(2)), Ok(vec![5]));
+        let compiled = CompiledProgram::compile(&problem).with_step_budget(2);
+        assert_eq!(run_compiled(&compiled, 5), Ok(vec![5]));
+
+        // bdv 6 (C=100); out 5; adv 6 -- shifts past the width of A
+        problem.c = 100;
+        problem.program = vec![6, 6, 5, 5, 0, 6, 5, 4];
+        let computer = Computer::new(&problem);
+        assert_eq!(computer.run(None), Ok(vec![0, 0]));
+    }
+
+    #[test]
+    fn compiled_matches_interpreter() {
+        let mut problem = Problem::new_from_input(TEST_INPUT_1).unwrap();
+        // include a jump to an odd address and an invalid combo operand
+        problem.program.extend([3, 1, 7, 5, 0, 7]);
+        let compiled = CompiledProgram::compile(&problem).with_step_budget(DEFAULT_STEP_BUDGET);
+        for a in 0..2000 {
+            let mut computer = Computer::new(&problem);
+            computer.a = a;
+            assert_eq!(computer.run(None), run_compiled(&compiled, a), "A={a}");
+        }
+
+        // out 4, then a lone invalid opcode: the program ends before it
+        problem.a = 5;
+        problem.program = vec![5, 4, 8];
+        let computer = Computer::new(&problem);
+        let compiled = CompiledProgram::compile(&problem);
+        assert_eq!(computer.run(None), Ok(vec![5]));
+        assert_eq!(run_compiled(&compiled, 5), Ok(vec![5]));
+        assert_eq!(compiled.outputs(5, &[5]), Ok(true));
+        assert_eq!(compiled.outputs(5, &[]), Ok(false));
+        assert_eq!(compiled.outputs(5, &[5, 0]), Ok(false));
+    }
 }
@@ -1,21 +1,19 @@
-use hashbrown::HashMap;
-use itertools::Itertools;
+use std::{fmt::Display, ops::AddAssign};
 
-pub fn day19(input: &str) -> (usize, usize) {
-    let (towels_owned, designs) = parse_input(input);
+use itertools::Itertools;
 
-    let mut towels = towels_owned.iter().map(String::as_str).collect_vec();
-    towels.sort();
+pub fn day19(input: &str) -> (usize, BigCount) {
+    let (towels, designs) = parse_input(input);
 
-    let mut cache = Default::default();
+    let trie = TowelTrie::new(&towels);
 
     let mut valid = 0;
-    let mut total_solutions = 0;
+    let mut total_solutions = BigCount::default();
 
     for design in &designs {
-        let count = make_design(design, &towels, &mut cache);
-        if count != 0 {
-            total_solutions += count;
+        let count = count_arrangements(design, &trie);
+        if !count.is_zero() {
+            total_solutions += &count;
             valid += 1;
         }
     }
@@ -23,6 +21,55 @@ pub fn day19(input: &str) -> (usize, usize) {
     (valid, total_solutions)
 }
 
+/// For each design, how many arrangements there are (modulo `--modulo M`, if
+/// given), the shortest one and the first `--first N` (default 3) in towel
+/// order
+pub fn day19arrangements(input: &str, args: &[&str]) -> String {
+    let mut first = 3;
+    let mut modulus = None;
+    for (option, value) in crate::option_pairs(args) {
+        match option {
+            "--first" => first = value.parse().unwrap(),
+            "--modulo" => {
+                let m: u64 = value.parse().unwrap();
+                assert!(m != 0, "--modulo must be at least 1");
+                modulus = Some(m);
+            }
+            _ => panic!("unknown option {option}"),
+        }
+    }
+
+    let (towels, designs) = parse_input(input);
+    let trie = TowelTrie::new(&towels);
+
+    let mut out = String::new();
+    let mut total = BigCount::default();
+    let mut total_modulo = modulus.map(Modular::zero);
+    for design in &designs {
+        let count = count_arrangements(design, &trie);
+        total += &count;
+        match modulus {
+            Some(m) => {
+                let count = count_in(design, &trie, Modular::one(m));
+                *total_modulo.as_mut().unwrap() += &count;
+                out += &format!("{design}: {count} arrangement(s) mod {m}\n");
+            }
+            None => out += &format!("{design}: {count} arrangement(s)\n"),
+        }
+        if let Some(shortest) = shortest_arrangement(design, &trie) {
+            out += &format!("  shortest: {}\n", shortest.join(" "));
+        }
+        for arrangement in Arrangements::new(design, &trie).take(first) {
+            out += &format!("  {}\n", arrangement.join(" "));
+        }
+    }
+    match (modulus, total_modulo) {
+        (Some(m), Some(total)) => out += &format!("total: {total} mod {m}\n"),
+        _ => out += &format!("total: {total}\n"),
+    }
+    out
+}
+
 fn parse_input(input: &str) -> (Vec<String>, Vec<String>) {
     let mut lines = input.trim().lines();
     let towels = lines
@@ -36,29 +83,304 @@ fn parse_input(input: &str) -> (Vec<String>, Vec<String>) {
     (towels, designs)
 }
 
-fn make_design<'d>(design: &'d str, towels: &[&str], cache: &mut HashMap<&'d str, usize>) -> usize {
-    if let Some(&answer) = cache.get(&design) {
-        return answer;
-    } else if design.is_empty() {
-        cache.insert(design, 1);
-        return 1;
+/// All the towels, as a trie over their colours
+#[derive(Debug, Clone)]
+struct TowelTrie {
+    nodes: Vec<TrieNode>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct TrieNode {
+    children: Vec<(u8, u32)>,
+    /// Whether some towel ends here
+    terminal: bool,
+}
+
+impl TowelTrie {
+    pub fn new(towels: &[impl AsRef<str>]) -> Self {
+        let mut trie = Self {
+            nodes: vec![TrieNode::default()],
+        };
+        for towel in towels {
+            let mut node = 0;
+            for colour in towel.as_ref().bytes() {
+                node = match trie.child(node, colour) {
+                    Some(child) => child,
+                    None => {
+                        let child = trie.nodes.len() as u32;
+                        trie.nodes.push(TrieNode::default());
+                        trie.nodes[node as usize].children.push((colour, child));
+                        child
+                    }
+                };
+            }
+            trie.nodes[node as usize].terminal = true;
+        }
+        trie
+    }
+
+    fn child(&self, node: u32, colour: u8) -> Option<u32> {
+        self.nodes[node as usize]
+            .children
+            .iter()
+            .find(|&&(c, _)| c == colour)
+            .map(|&(_, child)| child)
+    }
+
+    /// The lengths of the towels that `text` starts with, shortest first
+    /// (which is also sorted order, as they are all prefixes of `text`)
+    pub fn prefixes<'s>(&'s self, text: &'s str) -> impl Iterator<Item = usize> + 's {
+        let mut node = 0;
+        text.bytes()
+            .map_while(move |colour| {
+                node = self.child(node, colour)?;
+                Some(self.nodes[node as usize].terminal)
+            })
+            .positions(|terminal| terminal)
+            .map(|i| i + 1)
+    }
+}
+
+/// Number of ways to make a design. These grow exponentially with the length
+/// of the design, so they are counted without any upper limit.
+fn count_arrangements(design: &str, trie: &TowelTrie) -> BigCount {
+    count_in(design, trie, BigCount::from(1))
+}
+
+/// Number of ways to make a design, working forwards: every position that
+/// can be reached passes its count on to wherever a towel from there ends
+fn count_in<C: Count>(design: &str, trie: &TowelTrie, one: C) -> C {
+    let mut ways = vec![one.zero_like(); design.len() + 1];
+    ways[0] = one;
+    for pos in 0..design.len() {
+        if ways[pos].is_zero() {
+            continue;
+        }
+        let (done, rest) = ways.split_at_mut(pos + 1);
+        for len in trie.prefixes(&design[pos..]) {
+            rest[len - 1] += &done[pos];
+        }
+    }
+    ways.pop().unwrap()
+}
+
+/// Something arrangements can be counted in
+trait Count: Clone + for<'c> AddAssign<&'c Self> {
+    fn is_zero(&self) -> bool;
+
+    /// Zero, in the same setting as `self` (e.g. with the same modulus)
+    fn zero_like(&self) -> Self;
+}
+
+/// An unsigned integer of any size
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BigCount {
+    /// Least significant first, without trailing zeros
+    limbs: Vec<u64>,
+}
+
+impl From<u64> for BigCount {
+    fn from(value: u64) -> Self {
+        let limbs = if value == 0 { vec![] } else { vec![value] };
+        Self { limbs }
+    }
+}
+
+impl AddAssign<&BigCount> for BigCount {
+    fn add_assign(&mut self, other: &BigCount) {
+        if self.limbs.len() < other.limbs.len() {
+            self.limbs.resize(other.limbs.len(), 0);
+        }
+        let mut carry = false;
+        for (i, limb) in self.limbs.iter_mut().enumerate() {
+            let (sum, c1) = limb.overflowing_add(other.limbs.get(i).copied().unwrap_or(0));
+            let (sum, c2) = sum.overflowing_add(carry as u64);
+            *limb = sum;
+            carry = c1 || c2;
+        }
+        if carry {
+            self.limbs.push(1);
+        }
+    }
+}
+
+impl Count for BigCount {
+    fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    fn zero_like(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl Display for BigCount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // split into base 10^19 digits by long division, least significant first
+        const BASE: u64 = 10_000_000_000_000_000_000;
+        let mut limbs = self.limbs.clone();
+        let mut digits = vec![];
+        while !limbs.is_empty() {
+            let mut remainder = 0u128;
+            for limb in limbs.iter_mut().rev() {
+                let value = (remainder << 64) | *limb as u128;
+                *limb = (value / BASE as u128) as u64;
+                remainder = value % BASE as u128;
+            }
+            digits.push(remainder as u64);
+            while limbs.last() == Some(&0) {
+                limbs.pop();
+            }
+        }
+
+        let mut s = format!("{}", digits.pop().unwrap_or(0));
+        for digit in digits.iter().rev() {
+            s += &format!("{digit:019}");
+        }
+        f.pad(&s)
     }
+}
 
-    // find towels that *might* be usable for the start
-    let end = towels.partition_point(|&t| t <= design);
-    let begin = towels[..end].partition_point(|&t| t < &design[..1]);
+/// A count modulo some number (normally a prime)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Modular {
+    value: u64,
+    modulus: u64,
+}
 
-    let mut solutions_found = 0;
+impl Modular {
+    pub fn zero(modulus: u64) -> Self {
+        Self { value: 0, modulus }
+    }
 
-    for towel in &towels[begin..end] {
-        if let Some(rest) = design.strip_prefix(towel) {
-            let tail_solutions = make_design(rest, towels, cache);
-            solutions_found += tail_solutions;
+    pub fn one(modulus: u64) -> Self {
+        Self {
+            value: 1 % modulus,
+            modulus,
         }
     }
+}
+
+impl AddAssign<&Modular> for Modular {
+    fn add_assign(&mut self, other: &Modular) {
+        self.value = ((self.value as u128 + other.value as u128) % self.modulus as u128) as u64;
+    }
+}
+
+impl Count for Modular {
+    fn is_zero(&self) -> bool {
+        self.value == 0
+    }
 
-    cache.insert(design, solutions_found);
-    solutions_found
+    fn zero_like(&self) -> Self {
+        Self::zero(self.modulus)
+    }
+}
+
+impl Display for Modular {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.value, f)
+    }
+}
+
+/// Whether the rest of the design can be made from each position on
+fn finishable(design: &str, trie: &TowelTrie) -> Vec<bool> {
+    let mut finishable = vec![false; design.len() + 1];
+    finishable[design.len()] = true;
+    for pos in (0..design.len()).rev() {
+        finishable[pos] = trie
+            .prefixes(&design[pos..])
+            .any(|len| finishable[pos + len]);
+    }
+    finishable
+}
+
+/// Every way of making a design, as lists of towels, produced lazily. They
+/// come out in lexicographic order, so the first is the lexicographically
+/// first arrangement.
+struct Arrangements<'a> {
+    design: &'a str,
+    trie: &'a TowelTrie,
+    finishable: Vec<bool>,
+    /// Depth-first search state: a position in the design and the length of
+    /// the last towel tried there
+    frames: Vec<(usize, usize)>,
+    /// The towels taken to get to the top frame
+    path: Vec<&'a str>,
+}
+
+impl<'a> Arrangements<'a> {
+    pub fn new(design: &'a str, trie: &'a TowelTrie) -> Self {
+        let finishable = finishable(design, trie);
+        // dead ends are never entered
+        let frames = if finishable[0] { vec![(0, 0)] } else { vec![] };
+        Self {
+            design,
+            trie,
+            finishable,
+            frames,
+            path: vec![],
+        }
+    }
+}
+
+impl<'a> Iterator for Arrangements<'a> {
+    type Item = Vec<&'a str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((pos, last_len)) = self.frames.last_mut() {
+            let pos = *pos;
+            if pos == self.design.len() {
+                let arrangement = self.path.clone();
+                self.frames.pop();
+                self.path.pop();
+                return Some(arrangement);
+            }
+
+            let found = self
+                .trie
+                .prefixes(&self.design[pos..])
+                .find(|&len| len > *last_len && self.finishable[pos + len]);
+            match found {
+                Some(len) => {
+                    *last_len = len;
+                    self.path.push(&self.design[pos..pos + len]);
+                    self.frames.push((pos + len, 0));
+                }
+                None => {
+                    self.frames.pop();
+                    self.path.pop();
+                }
+            }
+        }
+        None
+    }
+}
+
+/// An arrangement with the fewest towels (the lexicographically first of
+/// those), if there is any
+fn shortest_arrangement<'a>(design: &'a str, trie: &TowelTrie) -> Option<Vec<&'a str>> {
+    // fewest towels to finish from each position, and the length of the
+    // towel to start with
+    let mut best: Vec<Option<(usize, usize)>> = vec![None; design.len() + 1];
+    best[design.len()] = Some((0, 0));
+    for pos in (0..design.len()).rev() {
+        best[pos] = trie
+            .prefixes(&design[pos..])
+            .filter_map(|len| Some((best[pos + len]?.0 + 1, len)))
+            .min_by_key(|&(n, _)| n);
+    }
+
+    best[0]?;
+    let mut arrangement = vec![];
+    let mut pos = 0;
+    while pos < design.len() {
+        // every step taken leads somewhere finishable
+        let (_, len) = best[pos].unwrap();
+        arrangement.push(&design[pos..pos + len]);
+        pos += len;
+    }
+    Some(arrangement)
 }
 
 #[cfg(test)]
@@ -85,6 +407,66 @@ mod test {
 
     #[test]
     fn part2test() {
-        assert_eq!(day19(TEST_INPUT).1, 16);
+        assert_eq!(day19(TEST_INPUT).1, BigCount::from(16));
+    }
+
+    #[test]
+    fn arrangements_test() {
+        let (towels, designs) = parse_input(TEST_INPUT);
+        let trie = TowelTrie::new(&towels);
+
+        let arrangements = Arrangements::new("brwrr", &trie).collect_vec();
+        assert_eq!(
+            arrangements,
+            [vec!["b", "r", "wr", "r"], vec!["br", "wr", "r"]]
+        );
+        assert_eq!(
+            shortest_arrangement("brwrr", &trie),
+            Some(vec!["br", "wr", "r"])
+        );
+        assert_eq!(Arrangements::new("ubwu", &trie).next(), None);
+        assert_eq!(shortest_arrangement("ubwu", &trie), None);
+
+        let total: usize = designs
+            .iter()
+            .map(|d| Arrangements::new(d, &trie).count())
+            .sum();
+        assert_eq!(total, 16);
+        for design in &designs {
+            for arrangement in Arrangements::new(design, &trie) {
+                assert_eq!(&arrangement.concat(), design);
+            }
+        }
+    }
+
+    #[test]
+    fn trie_test() {
+        let (towels, _) = parse_input(TEST_INPUT);
+        let trie = TowelTrie::new(&towels);
+        assert_eq!(trie.prefixes("bwurrg").collect_vec(), [1, 3]);
+        assert_eq!(trie.prefixes("gbbr").collect_vec(), [1, 2]);
+        assert_eq!(trie.prefixes("ubwu").count(), 0);
+
+        // far too deep for a recursive search
+        let design = "rb".repeat(50_000);
+        let trie = TowelTrie::new(&["r", "b"]);
+        assert_eq!(count_arrangements(&design, &trie), BigCount::from(1));
+    }
+
+    #[test]
+    fn big_count_test() {
+        // every "ab" is either "a" "b" or "ab"
+        let design = "ab".repeat(128);
+        let trie = TowelTrie::new(&["a", "b", "ab"]);
+        let count = count_arrangements(&design, &trie);
+        assert_eq!(count.to_string(), "340282366920938463463374607431768211456");
+        let count = count_in(&design, &trie, Modular::one(1_000_000_007));
+        assert_eq!(count.value, 279632277);
+
+        assert_eq!(BigCount::default().to_string(), "0");
+        let mut n = BigCount::from(u64::MAX);
+        n += &BigCount::from(1);
+        assert_eq!(n.to_string(), "18446744073709551616");
+        assert_eq!(format!("{:>5}", BigCount::from(42)), "   42");
     }
 }
@@ -1,4 +1,10 @@
-use std::{fmt::Debug, ops::Add, str::FromStr};
+use std::{
+    fmt::{Debug, Display},
+    ops::Add,
+    str::FromStr,
+};
+
+use itertools::Itertools;
 
 pub fn day20part1(input: &str) -> usize {
     let racetrack = RaceTrack::trace_map(input.parse().unwrap());
@@ -12,10 +18,94 @@ pub fn day20part2(input: &str) -> usize {
     shortcuts.len()
 }
 
+/// Cheats of up to `--radius R` (default 20) picoseconds that save at least
+/// `--min M` (default 1): how many save how much, the `--top N` (default 10)
+/// best, and, with `--show K`, the K-th of those drawn on the map. They are
+/// found by `--search rows` (default) or `--search offsets`.
+pub fn day20report(input: &str, args: &[&str]) -> String {
+    let mut radius = 20;
+    let mut min_benefit = 1;
+    let mut top = 10;
+    let mut show = None;
+    let mut search = CheatSearch::default();
+    for (option, value) in crate::option_pairs(args) {
+        match option {
+            "--search" => search = value.parse().unwrap(),
+            "--radius" => radius = value.parse().unwrap(),
+            "--min" => min_benefit = value.parse().unwrap(),
+            "--top" => top = value.parse().unwrap(),
+            "--show" => show = Some(value.parse::<usize>().unwrap()),
+            _ => panic!("unknown option {option}"),
+        }
+    }
+
+    let racetrack = RaceTrack::trace_map(input.parse().unwrap());
+    let mut shortcuts = racetrack.find_shortcuts_with(search, radius, min_benefit);
+    // best first
+    shortcuts.sort_by_key(|s| (-s.distance_saved, s.from, s.to));
+
+    let mut out = format!(
+        "{} cheats save at least {min_benefit} ps (best time without cheating: {} ps)\n",
+        shortcuts.len(),
+        racetrack.best_time()
+    );
+    let histogram = shortcuts.iter().map(|s| s.distance_saved).counts();
+    for (saved, count) in histogram.into_iter().sorted() {
+        out += &format!("  {count:>6} save {saved} ps\n");
+    }
+
+    out += &format!("top {top}:\n");
+    for (i, s) in shortcuts.iter().take(top).enumerate() {
+        let Shortcut {
+            distance_saved,
+            from: Pos(x1, y1),
+            to: Pos(x2, y2),
+        } = s;
+        out += &format!(
+            "  {:>3}. {distance_saved} ps: ({x1},{y1}) -> ({x2},{y2})\n",
+            i + 1
+        );
+    }
+
+    if let Some(k) = show {
+        let idx = k.checked_sub(1).expect("--show counts from 1");
+        let shortcut = shortcuts.get(idx).expect("no such cheat");
+        out += &racetrack.render(shortcut);
+    }
+    out
+}
+
+/// How to look for cheats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum CheatSearch {
+    /// Try every jump within reach of every position on the track
+    Offsets,
+    /// Try the positions in reach, found row by row in a spatial index
+    #[default]
+    Rows,
+}
+
+impl FromStr for CheatSearch {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "offsets" => Ok(Self::Offsets),
+            "rows" => Ok(Self::Rows),
+            _ => Err(()),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 struct RaceTrack {
     pub map: Map<RaceTrackTile>,
+    /// Every track position that can be reached from the start, nearest first
     pub track: Vec<Pos>,
+    /// Distance (without cheating) from the start to each position
+    pub from_start: Map<Option<i32>>,
+    /// Distance (without cheating) from each position to the end
+    pub to_end: Map<Option<i32>>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -25,57 +115,163 @@ struct Shortcut {
     pub to: Pos,
 }
 
-impl RaceTrack {
-    pub fn trace_map(mut map: Map<RaceTrackTile>) -> Self {
-        let mut track = vec![];
+const DIRS: [Direction; 4] = [
+    Direction::North,
+    Direction::South,
+    Direction::East,
+    Direction::West,
+];
 
+impl RaceTrack {
+    pub fn trace_map(map: Map<RaceTrackTile>) -> Self {
         let start = map.find_all(|&t| t == RaceTrackTile::Start).next().unwrap();
+        let end = map.find_all(|&t| t == RaceTrackTile::End).next().unwrap();
 
-        let mut pos = start;
-        let mut dist = 0;
+        let (from_start, track) = Self::distances_from(&map, start);
+        let (to_end, _) = Self::distances_from(&map, end);
 
-        loop {
-            track.push(pos);
-            match map.get(pos).copied() {
-                Some(RaceTrackTile::End) => break,
-                Some(RaceTrackTile::Start) => (),
-                Some(RaceTrackTile::Unexplored) => {
-                    map.set(pos, RaceTrackTile::Path(dist));
-                }
-                _ => unreachable!(),
-            }
-
-            const DIRS: [Direction; 4] = [
-                Direction::North,
-                Direction::South,
-                Direction::East,
-                Direction::West,
-            ];
+        Self {
+            map,
+            track,
+            from_start,
+            to_end,
+        }
+    }
 
-            let mut next_pos = None;
+    /// Breadth-first search over the track: the distance to every position,
+    /// and the positions in the order they were reached
+    fn distances_from(map: &Map<RaceTrackTile>, origin: Pos) -> (Map<Option<i32>>, Vec<Pos>) {
+        let mut distances = Map::filled(map.width(), map.height(), None);
+        distances.set(origin, Some(0));
+        let mut visited = vec![origin];
+        let mut i = 0;
+        while let Some(&pos) = visited.get(i) {
+            let dist = distances.get(pos).unwrap().unwrap();
             for dir in DIRS {
-                let candidate = pos + dir;
-                if matches!(
-                    map.get(candidate),
-                    Some(RaceTrackTile::Unexplored) | Some(RaceTrackTile::End)
-                ) {
-                    assert!(next_pos.is_none());
-                    next_pos = Some(candidate);
+                let next = pos + dir;
+                if matches!(map.get(next), Some(t) if *t != RaceTrackTile::Wall)
+                    && distances.get(next) == Some(&None)
+                {
+                    distances.set(next, Some(dist + 1));
+                    visited.push(next);
                 }
             }
+            i += 1;
+        }
+        (distances, visited)
+    }
 
-            assert!(next_pos.is_some());
-            pos = next_pos.unwrap();
-            dist += 1;
+    /// Length of the fastest route without cheating
+    pub fn best_time(&self) -> i32 {
+        self.track
+            .first()
+            .and_then(|&start| *self.to_end.get(start).unwrap())
+            .expect("the end cannot be reached")
+    }
+
+    /// The map with the route using a cheat: `1` where the cheat starts, `2`
+    /// where it ends, and `O` along the rest of the way
+    pub fn render(&self, shortcut: &Shortcut) -> String {
+        let mut tiles = self.map.matrix.iter().map(|t| t.to_string()).collect_vec();
+        let mut mark = |pos: Pos, c: &str| {
+            tiles[(pos.1 * self.map.width() + pos.0) as usize] = c.to_owned();
+        };
+
+        // walk downhill from both ends of the cheat
+        for (origin, distances) in [
+            (shortcut.from, &self.from_start),
+            (shortcut.to, &self.to_end),
+        ] {
+            let mut pos = origin;
+            let mut dist = distances.get(pos).unwrap().unwrap();
+            while dist > 1 {
+                dist -= 1;
+                pos = DIRS
+                    .iter()
+                    .map(|&dir| pos + dir)
+                    .find(|&next| distances.get(next) == Some(&Some(dist)))
+                    .unwrap();
+                mark(pos, "O");
+            }
         }
+        mark(shortcut.from, "1");
+        mark(shortcut.to, "2");
 
-        Self { map, track }
+        tiles
+            .chunks(self.map.width() as usize)
+            .map(|row| row.concat() + "\n")
+            .collect()
     }
 
     pub fn find_shortcuts(&self, max_dist: i32, min_benefit: i32) -> Vec<Shortcut> {
+        self.find_shortcuts_with(CheatSearch::default(), max_dist, min_benefit)
+    }
+
+    /// All cheats of 2 to `max_dist` steps that save at least `min_benefit`,
+    /// in no particular order (which depends on `search`)
+    pub fn find_shortcuts_with(
+        &self,
+        search: CheatSearch,
+        max_dist: i32,
+        min_benefit: i32,
+    ) -> Vec<Shortcut> {
+        match search {
+            CheatSearch::Offsets => self.find_shortcuts_by_offsets(max_dist, min_benefit),
+            CheatSearch::Rows => self.find_shortcuts_by_rows(max_dist, min_benefit),
+        }
+    }
+
+    fn find_shortcuts_by_rows(&self, max_dist: i32, min_benefit: i32) -> Vec<Shortcut> {
         let mut shortcuts = vec![];
 
-        let dist_to_end = (self.track.len() - 1) as i32;
+        let best_time = self.best_time();
+
+        // everywhere a cheat could end, by row, left to right
+        let mut rows = vec![vec![]; self.to_end.height() as usize];
+        for pos in self.to_end.find_all(Option::is_some) {
+            let remaining = self.to_end.get(pos).unwrap().unwrap();
+            rows[pos.1 as usize].push((pos.0, remaining));
+        }
+
+        for &pos in &self.track {
+            let orig_dist = self.from_start.get(pos).unwrap().unwrap();
+            // the jump and the rest of the way must fit in this
+            let budget = best_time - min_benefit - orig_dist;
+            let reach = max_dist.min(budget);
+            if reach < 2 {
+                continue;
+            }
+
+            let Pos(x, y) = pos;
+            for target_y in (y - reach).max(0)..=(y + reach).min(rows.len() as i32 - 1) {
+                let dy = (target_y - y).abs();
+                let max_dx = reach - dy;
+                let row = &rows[target_y as usize];
+                let first = row.partition_point(|&(tx, _)| tx < x - max_dx);
+
+                for &(target_x, remaining) in &row[first..] {
+                    if target_x > x + max_dx {
+                        break;
+                    }
+                    let jump_dist = (target_x - x).abs() + dy;
+                    if jump_dist >= 2 && jump_dist + remaining <= budget {
+                        shortcuts.push(Shortcut {
+                            distance_saved: best_time - (orig_dist + jump_dist + remaining),
+                            from: pos,
+                            to: Pos(target_x, target_y),
+                        });
+                    }
+                }
+            }
+        }
+
+        shortcuts
+    }
+
+    fn find_shortcuts_by_offsets(&self, max_dist: i32, min_benefit: i32) -> Vec<Shortcut> {
+        let mut shortcuts = vec![];
+
+        let best_time = self.best_time();
 
         // prepare all possible moves
         let mut moves = vec![];
@@ -98,29 +294,22 @@ impl RaceTrack {
             }
         }
 
-        for (i, &pos) in self.track.iter().enumerate() {
-            let orig_dist = i as i32;
+        for &pos in &self.track {
+            let orig_dist = self.from_start.get(pos).unwrap().unwrap();
 
             for &(dx, dy, jump_dist) in &moves {
                 let dist_after = orig_dist + jump_dist; // after the cheat
                 let target = Pos(pos.0 + dx, pos.1 + dy);
 
-                match self.map.get(target) {
-                    Some(RaceTrackTile::Path(d2)) if (d2 - dist_after) >= min_benefit => {
+                if let Some(&Some(remaining)) = self.to_end.get(target) {
+                    let distance_saved = best_time - (dist_after + remaining);
+                    if distance_saved >= min_benefit {
                         shortcuts.push(Shortcut {
-                            distance_saved: d2 - dist_after,
+                            distance_saved,
                             from: pos,
                             to: target,
                         });
                     }
-                    Some(RaceTrackTile::End) if (dist_to_end - dist_after) >= min_benefit => {
-                        shortcuts.push(Shortcut {
-                            distance_saved: dist_to_end - dist_after,
-                            from: pos,
-                            to: target,
-                        });
-                    }
-                    _ => {}
                 }
             }
         }
@@ -181,6 +370,13 @@ impl<Item: FromStr + Debug + Clone + Sized> FromStr for Map<Item> {
 }
 
 impl<Item: Debug + Clone + Sized> Map<Item> {
+    pub fn filled(width: i32, height: i32, value: Item) -> Self {
+        Self {
+            width,
+            matrix: vec![value; (width * height) as usize],
+        }
+    }
+
     pub fn width(&self) -> i32 {
         self.width
     }
@@ -227,8 +423,21 @@ enum RaceTrackTile {
     Start,
     End,
     Wall,
-    Unexplored,
-    Path(i32),
+    Track,
+}
+
+impl Display for RaceTrackTile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(
+            &match self {
+                Self::Start => 'S',
+                Self::End => 'E',
+                Self::Wall => '#',
+                Self::Track => '.',
+            },
+            f,
+        )
+    }
 }
 
 impl FromStr for RaceTrackTile {
@@ -237,7 +446,7 @@ impl FromStr for RaceTrackTile {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "S" => Ok(Self::Start),
-            "." => Ok(Self::Unexplored),
+            "." => Ok(Self::Track),
             "#" => Ok(Self::Wall),
             "E" => Ok(Self::End),
             _ => Err(()),
@@ -319,4 +528,79 @@ mod test {
         assert_eq!(counts.get(&74), Some(&4));
         assert_eq!(counts.get(&76), Some(&3));
     }
+
+    #[test]
+    fn search_test() {
+        let racetrack = RaceTrack::trace_map(TEST_INPUT.parse().unwrap());
+        for (max_dist, min_benefit) in [(2, 0), (2, 20), (20, 50), (20, 0), (6, -3)] {
+            let [mut by_offsets, mut by_rows] = [CheatSearch::Offsets, CheatSearch::Rows]
+                .map(|search| racetrack.find_shortcuts_with(search, max_dist, min_benefit));
+            by_offsets.sort();
+            by_rows.sort();
+            assert_eq!(by_offsets, by_rows);
+        }
+    }
+
+    #[test]
+    fn report_test() {
+        let report = day20report(
+            TEST_INPUT,
+            &["--radius", "2", "--min", "2", "--top", "2", "--show", "1"],
+        );
+        let lines = report.lines().collect_vec();
+        assert_eq!(
+            lines[0],
+            "44 cheats save at least 2 ps (best time without cheating: 84 ps)"
+        );
+        assert_eq!(lines[1].trim(), "14 save 2 ps");
+        assert_eq!(lines[11].trim(), "1 save 64 ps");
+        assert_eq!(lines[13].trim(), "1. 64 ps: (7,7) -> (5,7)");
+        assert_eq!(lines[14].trim(), "2. 40 ps: (7,7) -> (7,9)");
+        assert_eq!(lines[16], "#OOO#OOO#.....#");
+        assert_eq!(lines[22], "###..2#1..#...#");
+        assert_eq!(lines.len(), 30);
+    }
+
+    #[test]
+    fn branching_test() {
+        // a fork at the start, and a dead end next to the end
+        let racetrack = RaceTrack::trace_map(
+            "\
+            #######\n\
+            #S#..E#\n\
+            #.#.#.#\n\
+            #...#.#\n\
+            #######\n\
+            "
+            .parse()
+            .unwrap(),
+        );
+        assert_eq!(racetrack.track.len(), 11);
+        assert_eq!(racetrack.best_time(), 8);
+
+        let mut shortcuts = racetrack.find_shortcuts(2, 1);
+        shortcuts.sort();
+        assert_eq!(
+            shortcuts,
+            [
+                Shortcut {
+                    distance_saved: 2,
+                    from: Pos(1, 2),
+                    to: Pos(3, 2)
+                },
+                Shortcut {
+                    distance_saved: 4,
+                    from: Pos(1, 1),
+                    to: Pos(3, 1)
+                }
+            ]
+        );
+        // jumping into the dead end is allowed, but does not help
+        let into_dead_end = Shortcut {
+            distance_saved: 0,
+            from: Pos(3, 3),
+            to: Pos(5, 3),
+        };
+        assert!(racetrack.find_shortcuts(2, 0).contains(&into_dead_end));
+    }
 }
@@ -1,3 +1,5 @@
+use std::{collections::VecDeque, str::FromStr};
+
 use hashbrown::HashMap;
 
 pub fn day21part1(input: &str) -> usize {
@@ -32,13 +34,215 @@ pub fn day21part2(input: &str) -> usize {
     complexity
 }
 
-fn cost_to_enter_code(code: &str, n_robots: usize) -> usize {
+/// Print the sequence of buttons pressed at each level, for each code, with
+/// `--robots N` directional robots in between (default 2)
+pub fn day21sequences(input: &str, args: &[&str]) -> String {
+    let n_robots = match args {
+        ["--robots", n] => n.parse().unwrap(),
+        [] => 2,
+        _ => panic!("usage: 21 sequences [--robots N]"),
+    };
     let robot_stack = RobotStack::new(n_robots);
     let kp = Keypad::numeric();
+
+    let mut out = String::new();
+    for code in input.trim().lines() {
+        let sequences = robot_stack.sequences_to_enter(&kp, code);
+        assert_eq!(check_sequences(&robot_stack, &kp, code, &sequences), Ok(()));
+
+        out += &format!("{code}:\n");
+        out += &format!("  human: {}\n", sequences.last().unwrap());
+        for (i, seq) in sequences.iter().rev().skip(1).enumerate() {
+            out += &format!("  robot {}: {seq}\n", n_robots - i);
+        }
+        out += &format!("  door: {code}\n");
+    }
+    out
+}
+
+/// Replay every layer of a chain of sequences (as returned by
+/// [RobotStack::sequences_to_enter]) on its keypad, and make sure it produces
+/// the layer above. Returns the index of the first layer that doesn't.
+fn check_sequences(
+    robot_stack: &RobotStack,
+    kp: &Keypad,
+    code: &str,
+    sequences: &[String],
+) -> Result<(), usize> {
+    let keypads = std::iter::once(kp).chain(robot_stack.keypads());
+    let targets = std::iter::once(code).chain(sequences.iter().map(String::as_str));
+    for (i, ((kp, seq), target)) in keypads.zip(sequences).zip(targets).enumerate() {
+        if kp.replay(seq).as_deref() != Some(target) {
+            return Err(i);
+        }
+    }
+    Ok(())
+}
+
+/// Enter the codes through any chain of keypads: `--chain SPEC` (see [Chain]),
+/// `--keypad NAME=FILE` to load a custom keypad layout, and `--costs SPEC`
+/// (see [ButtonCosts])
+pub fn day21chain(input: &str, args: &[&str]) -> String {
+    let mut spec = "numeric <- directional*3 <- human";
+    let mut custom = HashMap::new();
+    let mut costs = ButtonCosts::default();
+    for (option, value) in crate::option_pairs(args) {
+        match option {
+            "--chain" => spec = value,
+            "--keypad" => {
+                let (name, path) = value.split_once('=').expect("expected NAME=FILE");
+                let layout = std::fs::read_to_string(path).unwrap();
+                custom.insert(name, layout.trim_end_matches('\n').parse().unwrap());
+            }
+            "--costs" => costs = value.parse().unwrap(),
+            _ => panic!("unknown option {option}"),
+        }
+    }
+    let chain = Chain::new(spec, &custom, &costs).unwrap();
+
+    let mut out = String::new();
+    let mut complexity = 0;
+    for code in input.trim().lines() {
+        let cost = chain.cost_to_enter(code);
+        out += &format!("{code}: {cost}\n");
+        if let Ok(numeric_part) = code.trim_end_matches('A').parse::<usize>() {
+            complexity += cost * numeric_part;
+        }
+    }
+    out + &format!("complexity: {complexity}\n")
+}
+
+/// What it costs the human to press each button: 1, unless set for that
+/// button (`A=3`) or for pressing that button straight after another one
+/// (`<A=5`), in a comma-separated list
+#[derive(Debug, Clone, Default)]
+struct ButtonCosts {
+    press: HashMap<char, usize>,
+    after: HashMap<(char, char), usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct InvalidCosts(String);
+
+impl FromStr for ButtonCosts {
+    type Err = InvalidCosts;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut costs = Self::default();
+        for item in s.split(',').filter(|item| !item.is_empty()) {
+            let invalid = || InvalidCosts(item.to_string());
+            let (buttons, cost) = item.split_once('=').ok_or_else(invalid)?;
+            let cost = cost.parse().map_err(|_| invalid())?;
+            match buttons.chars().collect::<Vec<_>>()[..] {
+                [button] => costs.press.insert(button, cost),
+                [from, to] => costs.after.insert((from, to), cost),
+                _ => return Err(invalid()),
+            };
+        }
+        Ok(costs)
+    }
+}
+
+impl ButtonCosts {
+    pub fn cost(&self, from: char, to: char) -> usize {
+        self.after
+            .get(&(from, to))
+            .or_else(|| self.press.get(&to))
+            .copied()
+            .unwrap_or(1)
+    }
+}
+
+/// A door keypad and the robots between it and the human, described like
+/// `numeric <- directional*2 <- custom <- human`. Each `<-` is a robot arm,
+/// pressing the keypad on its left and controlled from the keypad on its
+/// right; the human presses the last keypad.
+struct Chain {
+    door: Keypad,
+    robots: RobotStack,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ChainError {
+    UnknownKeypad(String),
+    InvalidRepeat(String),
+    NotDirectional(String),
+    NoHuman,
+    TooShort,
+}
+
+impl Chain {
+    pub fn new(
+        spec: &str,
+        custom: &HashMap<&str, Keypad>,
+        costs: &ButtonCosts,
+    ) -> Result<Self, ChainError> {
+        let mut names = vec![];
+        for item in spec.split("<-").map(str::trim) {
+            match item.split_once('*') {
+                Some((name, count)) => {
+                    let count = count
+                        .parse()
+                        .map_err(|_| ChainError::InvalidRepeat(item.to_string()))?;
+                    names.extend(std::iter::repeat_n(name.trim(), count));
+                }
+                None => names.push(item),
+            }
+        }
+
+        if names.pop() != Some("human") {
+            return Err(ChainError::NoHuman);
+        }
+        if names.len() < 2 {
+            return Err(ChainError::TooShort);
+        }
+        let keypads = names
+            .iter()
+            .map(|&name| match name {
+                "numeric" => Ok(Keypad::numeric()),
+                "directional" => Ok(Keypad::directional()),
+                _ => custom
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| ChainError::UnknownKeypad(name.to_string())),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (name, keypad) in names.iter().zip(&keypads).skip(1) {
+            if !"><^vA".chars().all(|c| keypad.keys.contains_key(&c)) {
+                return Err(ChainError::NotDirectional(name.to_string()));
+            }
+        }
+
+        // the human's keypad only matters for its labels
+        let mut robots = RobotStack::human(costs);
+        for keypad in keypads[1..keypads.len() - 1].iter().rev() {
+            robots = RobotStack::add_robot(robots, keypad);
+        }
+
+        Ok(Self {
+            door: keypads.into_iter().next().unwrap(),
+            robots,
+        })
+    }
+
+    pub fn cost_to_enter(&self, code: &str) -> usize {
+        cost_to_enter_code_on(code, &self.door, &self.robots)
+    }
+}
+
+fn cost_to_enter_code(code: &str, n_robots: usize) -> usize {
+    let robot_stack = RobotStack::new(n_robots);
+    cost_to_enter_code_on(code, &Keypad::numeric(), &robot_stack)
+}
+
+/// The cost to enter `code` on the keypad `kp`, which is operated by the
+/// robots in `robot_stack`
+fn cost_to_enter_code_on(code: &str, kp: &Keypad, robot_stack: &RobotStack) -> usize {
     let mut prev = 'A';
     let mut cost = 0;
     for c in code.chars() {
-        cost += robot_stack.cost_to_move_and_press(&kp, prev, c);
+        cost += robot_stack.cost_to_move_and_press(kp, prev, c);
         prev = c;
     }
     cost
@@ -47,35 +251,65 @@ fn cost_to_enter_code(code: &str, n_robots: usize) -> usize {
 struct RobotStack {
     /// (last thing we did, thing we want to do) -> cost of action
     costs: HashMap<(char, char), usize>,
+    /// The robot below this one (unless it's the human), with its keypad
+    below: Option<(Box<RobotStack>, Keypad)>,
+    /// (last thing we did, thing we want to do) -> a cheapest sequence of
+    /// presses for the robot below
+    choices: HashMap<(char, char), String>,
 }
 
 impl RobotStack {
     pub fn empty() -> Self {
+        Self::human(&ButtonCosts::default())
+    }
+
+    /// Just the human, pressing buttons at the given costs
+    pub fn human(button_costs: &ButtonCosts) -> Self {
         let costs = "><^vA"
             .chars()
-            .flat_map(|from| "><^vA".chars().map(move |to| ((from, to), 1)))
+            .flat_map(|from| {
+                "><^vA"
+                    .chars()
+                    .map(move |to| ((from, to), button_costs.cost(from, to)))
+            })
             .collect();
 
-        Self { costs }
+        Self {
+            costs,
+            below: None,
+            choices: HashMap::new(),
+        }
     }
 
-    pub fn add_robot(implement: &RobotStack) -> Self {
-        let keypad = Keypad::directional();
+    /// Add a robot on top of `implement`, pressing the keys on `keypad`,
+    /// which has to be some arrangement of the keys `><^vA`
+    pub fn add_robot(implement: RobotStack, keypad: &Keypad) -> Self {
         let mut costs = HashMap::new();
+        let mut choices = HashMap::new();
         for from in "><^vA".chars() {
             for to in "><^vA".chars() {
-                let cost = implement.cost_to_move_and_press(&keypad, from, to);
+                let (seq, cost) = implement.best_sequence(keypad, from, to);
                 costs.insert((from, to), cost);
+                choices.insert((from, to), seq);
             }
         }
 
-        Self { costs }
+        Self {
+            costs,
+            below: Some((Box::new(implement), keypad.clone())),
+            choices,
+        }
     }
 
     pub fn new(depth: usize) -> Self {
+        Self::with_keypad(depth, &Keypad::directional())
+    }
+
+    /// A stack of `depth` robots that all use the same directional keypad
+    pub fn with_keypad(depth: usize, keypad: &Keypad) -> Self {
         let mut stack = RobotStack::empty();
         for _ in 0..depth {
-            let next_layer = RobotStack::add_robot(&stack);
+            let next_layer = RobotStack::add_robot(stack, keypad);
             stack = next_layer;
         }
         stack
@@ -83,11 +317,58 @@ impl RobotStack {
 
     // we're hovering at "from" and want to press "sym"
     pub fn cost_to_move_and_press(&self, kp: &Keypad, from: char, sym: char) -> usize {
+        self.best_sequence(kp, from, sym).1
+    }
+
+    /// The cheapest way to move from `from` to `sym` on `kp` and press it,
+    /// and what that costs
+    fn best_sequence(&self, kp: &Keypad, from: char, sym: char) -> (String, usize) {
         kp.get_sequences(from, sym)
             .into_iter()
-            .map(|s| self.cost_of_seq(&s))
-            .min()
-            .unwrap()
+            .map(|s| {
+                let cost = self.cost_of_seq(&s);
+                (s, cost)
+            })
+            .min_by_key(|(_, cost)| *cost)
+            .expect("no path between keys")
+    }
+
+    /// One of the cheapest ways to enter `code` on `kp`: the sequence each
+    /// robot has to press, from the one at `kp` down to the human
+    pub fn sequences_to_enter(&self, kp: &Keypad, code: &str) -> Vec<String> {
+        let mut prev = 'A';
+        let mut seq = String::new();
+        for c in code.chars() {
+            seq += &self.best_sequence(kp, prev, c).0;
+            prev = c;
+        }
+
+        let mut sequences = vec![];
+        let mut layer = self;
+        while let Some((below, _)) = &layer.below {
+            let mut prev = 'A';
+            let mut next_seq = String::new();
+            for c in seq.chars() {
+                next_seq += &layer.choices[&(prev, c)];
+                prev = c;
+            }
+            sequences.push(seq);
+            seq = next_seq;
+            layer = below;
+        }
+        sequences.push(seq);
+        sequences
+    }
+
+    /// The keypads of all the robots, from the top down
+    pub fn keypads(&self) -> Vec<&Keypad> {
+        let mut keypads = vec![];
+        let mut layer = self;
+        while let Some((below, keypad)) = &layer.below {
+            keypads.push(keypad);
+            layer = below;
+        }
+        keypads
     }
 
     /// the cost to perform a sequence of actions
@@ -105,80 +386,147 @@ impl RobotStack {
 #[derive(Debug, Clone)]
 struct Keypad {
     keys: HashMap<char, (i8, i8)>,
-    blank: (i8, i8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InvalidLayout {
+    DuplicateKey(char),
+    NoAKey,
+}
+
+/// Read a keypad from a picture of it, one line per row, with a space
+/// wherever there is a gap (and keys labelled with any other character)
+impl FromStr for Keypad {
+    type Err = InvalidLayout;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rows = s.lines().collect::<Vec<_>>();
+        let mut keys = HashMap::new();
+        for (row_idx, row) in rows.iter().enumerate() {
+            // the bottom row is y=0
+            let y = (rows.len() - 1 - row_idx) as i8;
+            for (x, c) in row.chars().enumerate() {
+                if c != ' ' && keys.insert(c, (x as i8, y)).is_some() {
+                    return Err(InvalidLayout::DuplicateKey(c));
+                }
+            }
+        }
+
+        if !keys.contains_key(&'A') {
+            return Err(InvalidLayout::NoAKey);
+        }
+        Ok(Self { keys })
+    }
 }
 
 impl Keypad {
     pub fn numeric() -> Self {
-        let keys = [
-            ('0', (1, 0)),
-            ('A', (2, 0)),
-            ('1', (0, 1)),
-            ('2', (1, 1)),
-            ('3', (2, 1)),
-            ('4', (0, 2)),
-            ('5', (1, 2)),
-            ('6', (2, 2)),
-            ('7', (0, 3)),
-            ('8', (1, 3)),
-            ('9', (2, 3)),
-        ]
-        .into_iter()
-        .collect();
-        let blank = (0, 0);
-
-        Self { keys, blank }
+        "789\n456\n123\n 0A".parse().unwrap()
     }
 
     pub fn directional() -> Self {
-        let keys = [
-            ('<', (0, 0)),
-            ('v', (1, 0)),
-            ('>', (2, 0)),
-            ('^', (1, 1)),
-            ('A', (2, 1)),
-        ]
-        .into_iter()
-        .collect();
-        let blank = (0, 1);
-
-        Self { keys, blank }
+        " ^A\n<v>".parse().unwrap()
     }
 
     /// Assuming your robot is pointing at `from`, what would you have to enter
     /// in the d-pad to press `key`
     ///
-    /// Returns all direct L-shaped options
+    /// Returns every shortest path that doesn't pass over a gap
     pub fn get_sequences(&self, from: char, key: char) -> Vec<String> {
         let from = *self.keys.get(&from).unwrap();
         let dest = *self.keys.get(&key).unwrap();
-        let delta = (dest.0 - from.0, dest.1 - from.1);
 
         let mut paths = vec![];
+        let mut path = String::new();
+        self.monotone_paths(from, dest, &mut path, &mut paths);
+        if paths.is_empty() {
+            // the gaps are in the way: the shortest paths go around them
+            let distances = self.distances_to(dest);
+            if distances.contains_key(&from) {
+                self.shortest_paths(from, &distances, &mut path, &mut paths);
+            }
+        }
+        paths
+    }
+
+    /// Depth-first search of all paths that only ever move towards `dest`
+    fn monotone_paths(
+        &self,
+        pos: (i8, i8),
+        dest: (i8, i8),
+        path: &mut String,
+        paths: &mut Vec<String>,
+    ) {
+        if pos == dest {
+            paths.push(format!("{path}A"));
+            return;
+        }
 
-        let x_sym = ['<', '>'][if delta.0 >= 0 { 1 } else { 0 }];
-        let y_sym = ['v', '^'][if delta.1 >= 0 { 1 } else { 0 }];
-        let x_subseq: String = std::iter::repeat_n(x_sym, delta.0.abs() as usize).collect();
-        let y_subseq: String = std::iter::repeat_n(y_sym, delta.1.abs() as usize).collect();
-
-        if self.blank.0 == from.0 && self.blank.1 == dest.1 {
-            // must move x first
-            paths.push(format!("{}{}A", &x_subseq, &y_subseq));
-        } else if self.blank.1 == from.1 && self.blank.0 == dest.0 {
-            // must move y first
-            paths.push(format!("{}{}A", &y_subseq, &x_subseq));
-        } else {
-            // either way is fine
-            paths.push(format!("{}{}A", &y_subseq, &x_subseq));
-            if delta.0 != 0 && delta.1 != 0 {
-                paths.push(format!("{}{}A", &x_subseq, &y_subseq));
+        let x_step = (dest.0 - pos.0).signum();
+        let y_step = (dest.1 - pos.1).signum();
+        let steps = [
+            (x_step != 0).then_some(((pos.0 + x_step, pos.1), if x_step > 0 { '>' } else { '<' })),
+            (y_step != 0).then_some(((pos.0, pos.1 + y_step), if y_step > 0 { '^' } else { 'v' })),
+        ];
+        for (next, sym) in steps.into_iter().flatten() {
+            if self.keys.values().any(|&p| p == next) {
+                path.push(sym);
+                self.monotone_paths(next, dest, path, paths);
+                path.pop();
             }
         }
+    }
 
-        paths
+    /// Breadth-first search from `dest` over the keys: how many moves it
+    /// takes to get there from every key that can reach it
+    fn distances_to(&self, dest: (i8, i8)) -> HashMap<(i8, i8), usize> {
+        let mut distances = HashMap::from([(dest, 0)]);
+        let mut queue = VecDeque::from([dest]);
+        while let Some(pos) = queue.pop_front() {
+            let dist = distances[&pos];
+            for (next, _) in Self::neighbours(pos) {
+                if self.keys.values().any(|&p| p == next) && !distances.contains_key(&next) {
+                    distances.insert(next, dist + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+        distances
+    }
+
+    /// Depth-first search of all paths that get one move closer to the
+    /// destination of `distances` with every move
+    fn shortest_paths(
+        &self,
+        pos: (i8, i8),
+        distances: &HashMap<(i8, i8), usize>,
+        path: &mut String,
+        paths: &mut Vec<String>,
+    ) {
+        let dist = distances[&pos];
+        if dist == 0 {
+            paths.push(format!("{path}A"));
+            return;
+        }
+
+        for (next, sym) in Self::neighbours(pos) {
+            if distances.get(&next) == Some(&(dist - 1)) {
+                path.push(sym);
+                self.shortest_paths(next, distances, path, paths);
+                path.pop();
+            }
+        }
+    }
+
+    fn neighbours(pos: (i8, i8)) -> [((i8, i8), char); 4] {
+        [
+            ((pos.0 + 1, pos.1), '>'),
+            ((pos.0 - 1, pos.1), '<'),
+            ((pos.0, pos.1 + 1), '^'),
+            ((pos.0, pos.1 - 1), 'v'),
+        ]
     }
 
-    #[allow(unused)]
     pub fn replay(&self, moves: &str) -> Option<String> {
         let keys_by_pos: HashMap<_, _> = self.keys.iter().map(|(&k, &v)| (v, k)).collect();
 
@@ -233,14 +581,119 @@ mod test {
         let mut paths_to_2 = numpad.get_sequences('A', '2');
         paths_to_2.sort();
         assert_eq!(&paths_to_2, &["<^A".to_string(), "^<A".to_string()]);
-        let paths_to_1 = numpad.get_sequences('A', '1');
-        assert_eq!(&paths_to_1, &["^<<A".to_string()]);
+        let mut paths_to_1 = numpad.get_sequences('A', '1');
+        paths_to_1.sort();
+        assert_eq!(&paths_to_1, &["<^<A".to_string(), "^<<A".to_string()]);
         let mut paths_1_to_9 = numpad.get_sequences('1', '9');
         paths_1_to_9.sort();
-        assert_eq!(&paths_1_to_9, &[">>^^A".to_string(), "^^>>A".to_string()]);
+        assert_eq!(
+            &paths_1_to_9,
+            &[">>^^A", ">^>^A", ">^^>A", "^>>^A", "^>^>A", "^^>>A"]
+        );
+
+        let mut paths_to_left = dpad.get_sequences('A', '<');
+        paths_to_left.sort();
+        assert_eq!(&paths_to_left, &["<v<A".to_string(), "v<<A".to_string()]);
+    }
+
+    #[test]
+    fn custom_layout_test() {
+        // a phone-style keypad with gaps in the middle rows
+        let phone: Keypad = "123\n4 6\n7 9\n*0A".parse().unwrap();
+        assert_eq!(&phone.get_sequences('4', '6'), &["^>>vA"]);
+        assert_eq!(&phone.get_sequences('1', '9'), &[">>vvA"]);
+        assert_eq!(&phone.get_sequences('7', '3'), &["^^>>A"]);
+        assert_eq!(
+            cost_to_enter_code_on("7*9A", &phone, &RobotStack::empty()),
+            12
+        );
+
+        // the directional keypad upside down
+        let dpad: Keypad = "<^>\n Av".parse().unwrap();
+        let robots = RobotStack::with_keypad(1, &dpad);
+        assert!(cost_to_enter_code_on("7*9A", &phone, &robots) > 12);
+
+        // 4 to 6 has to go around the gap
+        assert_eq!(
+            cost_to_enter_code_on("46A", &phone, &RobotStack::empty()),
+            13
+        );
+        let robots = RobotStack::new(2);
+        let sequences = robots.sequences_to_enter(&phone, "46A");
+        assert_eq!(phone.replay(&sequences[0]).as_deref(), Some("46A"));
+
+        assert_eq!(
+            "AA".parse::<Keypad>().unwrap_err(),
+            InvalidLayout::DuplicateKey('A')
+        );
+        assert_eq!("123".parse::<Keypad>().unwrap_err(), InvalidLayout::NoAKey);
+    }
 
-        let paths_to_left = dpad.get_sequences('A', '<');
-        assert_eq!(&paths_to_left, &["v<<A".to_string()]);
+    #[test]
+    fn chain_test() {
+        let no_custom = HashMap::new();
+        let unit_costs = ButtonCosts::default();
+        let part1 =
+            Chain::new("numeric <- directional*3 <- human", &no_custom, &unit_costs).unwrap();
+        assert_eq!(part1.cost_to_enter("029A"), 68);
+        let deep = Chain::new(
+            "numeric <- directional*26 <- human",
+            &no_custom,
+            &unit_costs,
+        )
+        .unwrap();
+        assert_eq!(deep.cost_to_enter("029A"), cost_to_enter_code("029A", 25));
+
+        // the human's own keypad can have any layout
+        let custom = HashMap::from([("flipped", "<^>\n Av".parse().unwrap())]);
+        let mixed = Chain::new(
+            "numeric <- flipped <- directional <- flipped <- human",
+            &custom,
+            &unit_costs,
+        )
+        .unwrap();
+        let cost = mixed.cost_to_enter("029A");
+        assert_ne!(cost, 68);
+        let expensive_a = "A=2".parse().unwrap();
+        let mixed = Chain::new(
+            "numeric <- flipped <- directional <- flipped <- human",
+            &custom,
+            &expensive_a,
+        )
+        .unwrap();
+        assert!(mixed.cost_to_enter("029A") > cost);
+
+        let errors = [
+            ("numeric <- directional", ChainError::NoHuman),
+            ("numeric <- human", ChainError::TooShort),
+            (
+                "numeric <- numeric <- human",
+                ChainError::NotDirectional("numeric".to_string()),
+            ),
+            (
+                "numeric <- phone <- human",
+                ChainError::UnknownKeypad("phone".to_string()),
+            ),
+            (
+                "numeric <- directional*x <- human",
+                ChainError::InvalidRepeat("directional*x".to_string()),
+            ),
+        ];
+        for (spec, error) in errors {
+            assert_eq!(Chain::new(spec, &custom, &unit_costs).err(), Some(error));
+        }
+    }
+
+    #[test]
+    fn sequences_test() {
+        let robots = RobotStack::new(2);
+        let kp = Keypad::numeric();
+        let sequences = robots.sequences_to_enter(&kp, "029A");
+        assert_eq!(sequences.len(), 3);
+        assert_eq!(sequences[0].len(), "<A^A>^^AvvvA".len());
+        assert_eq!(sequences[2].len(), 68);
+        assert_eq!(check_sequences(&robots, &kp, "029A", &sequences), Ok(()));
+        assert_eq!(check_sequences(&robots, &kp, "028A", &sequences), Err(0));
     }
 
     #[test]
@@ -1,77 +1,98 @@
-use hashbrown::{HashMap, HashSet};
 use itertools::Itertools;
 
 pub fn day22part1(input: &str) -> u64 {
     let numbers: Vec<u32> = input.lines().filter_map(|s| s.parse().ok()).collect();
 
-    let mut sum = 0;
-    for seed in numbers {
-        let mut n = seed;
-        for _ in 0..2000 {
-            n = monkey(n);
-        }
-        sum += n as u64;
+    let jump = Gf2Matrix::monkey().pow(2000);
+
+    numbers
+        .into_iter()
+        .map(|seed| jump.apply(seed) as u64)
+        .sum()
+}
+
+/// For every buyer: the seed, the 2000th secret, the seed worked back out
+/// from that, and how long the seed's cycle is; then how all possible
+/// secrets split up into cycles
+pub fn day22orbits(input: &str, _args: &[&str]) -> String {
+    let seeds: Vec<u32> = input.lines().filter_map(|s| s.parse().ok()).collect();
+    let jump = Gf2Matrix::monkey().pow(2000);
+    let orbits = Orbits::find();
+
+    let mut out = String::new();
+    for seed in seeds {
+        let secret = jump.apply(seed);
+        let recovered = recover_seed(secret, 2000);
+        let cycle = orbits.cycle_length(seed);
+        out += &format!("{seed} -> {secret} -> {recovered} (cycle length {cycle})\n");
     }
-    sum
+    for (length, count) in orbits.structure() {
+        out += &format!("{count} cycle(s) of length {length}\n");
+    }
+    out
 }
 
 pub fn day22part2(input: &str) -> u64 {
     let seeds: Vec<u32> = input.lines().filter_map(|s| s.parse().ok()).collect();
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
 
-    let mut all_prices = vec![];
+    let totals = bananas_by_sequence(&seeds, threads);
+    totals.into_iter().max().unwrap_or_default() as u64
+}
 
-    for seed in seeds {
-        let mut prices = vec![];
-        let mut n = seed;
-        for _ in 0..2000 {
-            n = monkey(n);
-            let price = (n % 10) as i8;
-            prices.push(price);
-        }
-        all_prices.push(prices);
-    }
-
-    let diffs = all_prices
-        .iter()
-        .map(|prices| {
-            prices
-                .iter()
-                .zip(&prices[1..])
-                .map(|(&p1, &p2)| p2 - p1)
-                .collect_vec()
-        })
-        .collect_vec();
-
-    let all_instructions = diffs
-        .iter()
-        .zip(&all_prices)
-        .map(|(diffs, prices)| {
-            let mut instructions = HashMap::new();
-            for i in 3..diffs.len() {
-                let price = prices[i + 1];
-                let seq = (diffs[i - 3], diffs[i - 2], diffs[i - 1], diffs[i]);
-                instructions.entry(seq).or_insert(price);
-            }
-            instructions
-        })
-        .collect_vec();
+/// Price changes run from -9 to 9, so there are 19 of them
+const N_CHANGES: usize = 19;
+/// Number of possible sequences of four price changes
+const N_SEQUENCES: usize = N_CHANGES.pow(4);
 
-    let possible_instruction: HashSet<_> = all_instructions
-        .iter()
-        .flat_map(|ii| ii.keys().copied())
-        .collect();
+/// For every sequence of four price changes (encoded as a base-19 number),
+/// how many bananas we'd get in total by selling at the first occurrence for
+/// each buyer. The buyers are split between `threads` threads.
+fn bananas_by_sequence(seeds: &[u32], threads: usize) -> Vec<u32> {
+    let chunk_size = seeds.len().div_ceil(threads.max(1)).max(1);
 
-    let mut max_bananas = 0;
+    std::thread::scope(|scope| {
+        let handles = seeds
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(|| bananas_for_buyers(chunk)))
+            .collect_vec();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .reduce(|mut totals, part| {
+                totals.iter_mut().zip(part).for_each(|(t, p)| *t += p);
+                totals
+            })
+            .unwrap_or_else(|| vec![0; N_SEQUENCES])
+    })
+}
 
-    for instr in possible_instruction {
-        let bananas = all_instructions
-            .iter()
-            .map(|ii| ii.get(&instr).copied().unwrap_or_default() as u64)
-            .sum::<u64>();
-        max_bananas = max_bananas.max(bananas);
+fn bananas_for_buyers(seeds: &[u32]) -> Vec<u32> {
+    let mut totals = vec![0; N_SEQUENCES];
+    // seen[seq] == buyer number + 1 if we've already sold to this buyer
+    let mut seen = vec![0; N_SEQUENCES];
+
+    for (buyer, &seed) in seeds.iter().enumerate() {
+        let stamp = buyer as u32 + 1;
+        let mut n = monkey(seed);
+        let mut prev_price = n % 10;
+        let mut seq = 0;
+
+        for i in 1..2000 {
+            n = monkey(n);
+            let price = n % 10;
+            let change = (price + 9 - prev_price) as usize;
+            seq = (seq * N_CHANGES + change) % N_SEQUENCES;
+            prev_price = price;
+
+            if i >= 4 && seen[seq] != stamp {
+                seen[seq] = stamp;
+                totals[seq] += price;
+            }
+        }
     }
 
-    max_bananas
+    totals
 }
 
 fn monkey(mut n: u32) -> u32 {
@@ -81,6 +102,125 @@ fn monkey(mut n: u32) -> u32 {
     n
 }
 
+/// The secret that [monkey] turns into `n`: each of its three xorshifts can be
+/// undone by xoring in enough further shifted copies
+fn unmonkey(mut n: u32) -> u32 {
+    n = (n ^ (n << 11) ^ (n << 22)) & 0xffffff;
+    n = n ^ (n >> 5) ^ (n >> 10) ^ (n >> 15) ^ (n >> 20);
+    n = (n ^ (n << 6) ^ (n << 12) ^ (n << 18)) & 0xffffff;
+    n
+}
+
+/// Number of bits in a secret
+const SECRET_BITS: usize = 24;
+
+/// A linear map on secrets, over GF(2): column `i` is the image of bit `i`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Gf2Matrix {
+    columns: [u32; SECRET_BITS],
+}
+
+impl Gf2Matrix {
+    /// The matrix of any linear function on secrets (such as [monkey])
+    pub fn from_fn(f: impl Fn(u32) -> u32) -> Self {
+        Self {
+            columns: std::array::from_fn(|i| f(1 << i)),
+        }
+    }
+
+    pub fn identity() -> Self {
+        Self::from_fn(|n| n)
+    }
+
+    pub fn monkey() -> Self {
+        Self::from_fn(monkey)
+    }
+
+    pub fn unmonkey() -> Self {
+        Self::from_fn(unmonkey)
+    }
+
+    pub fn apply(&self, n: u32) -> u32 {
+        (0..SECRET_BITS)
+            .filter(|i| n & (1 << i) != 0)
+            .fold(0, |acc, i| acc ^ self.columns[i])
+    }
+
+    /// `self` after `other`
+    pub fn compose(&self, other: &Self) -> Self {
+        Self {
+            columns: other.columns.map(|col| self.apply(col)),
+        }
+    }
+
+    /// `self` applied `n` times, by repeated squaring
+    pub fn pow(&self, mut n: u64) -> Self {
+        let mut result = Self::identity();
+        let mut square = *self;
+        while n != 0 {
+            if n & 1 != 0 {
+                result = result.compose(&square);
+            }
+            square = square.compose(&square);
+            n >>= 1;
+        }
+        result
+    }
+}
+
+/// The seed that produces `secret` after `steps` steps
+fn recover_seed(secret: u32, steps: u64) -> u32 {
+    Gf2Matrix::unmonkey().pow(steps).apply(secret)
+}
+
+/// How all 2^24 secrets split up into cycles (the generator is a
+/// permutation of the secrets, so every secret comes around again)
+struct Orbits {
+    /// For each secret, which cycle it is on, counting from 1
+    cycle_ids: Vec<u32>,
+    /// The length of each cycle, by id - 1
+    lengths: Vec<u32>,
+}
+
+impl Orbits {
+    pub fn find() -> Self {
+        let mut cycle_ids = vec![0; 1 << SECRET_BITS];
+        let mut lengths = vec![];
+        for seed in 0..1 << SECRET_BITS {
+            if cycle_ids[seed as usize] != 0 {
+                continue;
+            }
+            let id = lengths.len() as u32 + 1;
+            let mut n = seed;
+            let mut length = 0;
+            while cycle_ids[n as usize] == 0 {
+                cycle_ids[n as usize] = id;
+                n = monkey(n);
+                length += 1;
+            }
+            lengths.push(length);
+        }
+        Self { cycle_ids, lengths }
+    }
+
+    /// Number of steps until `seed` comes around again
+    pub fn cycle_length(&self, seed: u32) -> u32 {
+        self.lengths[self.cycle_ids[seed as usize] as usize - 1]
+    }
+
+    /// The cycle lengths there are, and how many cycles have each
+    pub fn structure(&self) -> Vec<(u32, u32)> {
+        self.lengths
+            .iter()
+            .copied()
+            .counts()
+            .into_iter()
+            .map(|(l, n)| (l, n as u32))
+            .sorted()
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -96,4 +236,52 @@ mod test {
     fn part1test() {
         assert_eq!(day22part1(TEST_INPUT), 37327623);
     }
+
+    #[test]
+    fn generator_test() {
+        let mut n = 123;
+        let mut secrets = vec![n];
+        for _ in 0..10 {
+            n = monkey(n);
+            secrets.push(n);
+        }
+        assert_eq!(secrets[1], 15887950);
+        assert_eq!(secrets[10], 5908254);
+
+        for (i, &secret) in secrets.iter().enumerate() {
+            assert_eq!(unmonkey(monkey(secret)), secret);
+            assert_eq!(Gf2Matrix::monkey().pow(i as u64).apply(123), secret);
+            assert_eq!(recover_seed(secret, i as u64), 123);
+        }
+        let m = Gf2Matrix::monkey();
+        assert_eq!(m.compose(&Gf2Matrix::unmonkey()), Gf2Matrix::identity());
+        assert_eq!(m.pow(1000).compose(&m.pow(1000)), m.pow(2000));
+    }
+
+    #[test]
+    fn orbits_test() {
+        let out = day22orbits(TEST_INPUT, &[]);
+        assert_eq!(
+            out,
+            "\
+            1 -> 8685429 -> 1 (cycle length 16777215)\n\
+            10 -> 4700978 -> 10 (cycle length 16777215)\n\
+            100 -> 15273692 -> 100 (cycle length 16777215)\n\
+            2024 -> 8667524 -> 2024 (cycle length 16777215)\n\
+            1 cycle(s) of length 1\n\
+            1 cycle(s) of length 16777215\n\
+            "
+        );
+    }
+
+    #[test]
+    fn part2test() {
+        let seeds = [1, 2, 3, 2024];
+        let serial = bananas_by_sequence(&seeds, 1);
+        assert_eq!(serial, bananas_by_sequence(&seeds, 3));
+        // -2,1,-1,3
+        let seq = [7, 10, 8, 12].iter().fold(0, |seq, c| seq * N_CHANGES + c);
+        assert_eq!(serial[seq], 23);
+        assert_eq!(serial.iter().max(), Some(&23));
+    }
 }
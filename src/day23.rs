@@ -1,161 +1,507 @@
-use core::str;
-use std::{
-    cmp::Ordering,
-    fmt::{Debug, Display},
-    str::FromStr,
-};
+use std::collections::{BTreeMap, VecDeque};
 
 use hashbrown::{HashMap, HashSet};
 use itertools::Itertools;
 
 pub fn day23part1(input: &str) -> usize {
-    let edges = parse_edges(input);
-    let mut vertices: HashMap<ComputerName, Vec<ComputerName>> = HashMap::new();
+    let graph = Graph::new(&parse_edges(input));
 
-    for (a, b) in edges {
-        let a_neighbours = vertices.entry(a).or_default();
-        a_neighbours.push(b);
-        a_neighbours.sort();
-        let b_neighbours = vertices.entry(b).or_default();
-        b_neighbours.push(a);
-        b_neighbours.sort();
+    // find the 3-loops where one starts with t
+    graph.count_cliques(3, |v| graph.name(v).starts_with("t"))
+}
+
+pub fn day23part2(input: &str) -> String {
+    let graph = Graph::new(&parse_edges(input));
+
+    // the question implies there is only one; if not, take the first
+    let cliques = graph.maximum_cliques();
+
+    graph.join(&cliques[0])
+}
+
+/// List cliques, one per line: by default the maximum ones, with `--all`
+/// every maximal one
+pub fn day23cliques(input: &str, args: &[&str]) -> String {
+    let graph = Graph::new(&parse_edges(input));
+    let cliques = match args {
+        [] => graph.maximum_cliques(),
+        ["--all"] => graph.maximal_cliques(),
+        _ => panic!("unexpected arguments {args:?}"),
+    };
+    cliques
+        .iter()
+        .map(|clique| graph.join(clique) + "\n")
+        .collect()
+}
+
+/// Count the cliques of `--size K` (default 3) with at least one member whose
+/// name starts with `--prefix P` (default: any name)
+pub fn day23count(input: &str, args: &[&str]) -> String {
+    let graph = Graph::new(&parse_edges(input));
+    let mut size = 3;
+    let mut prefix = "";
+    for (option, value) in crate::option_pairs(args) {
+        match option {
+            "--size" => size = value.parse().unwrap(),
+            "--prefix" => prefix = value,
+            _ => panic!("unknown option {option}"),
+        }
     }
+    let count = graph.count_cliques(size, |v| graph.name(v).starts_with(prefix));
+    format!("{count}\n")
+}
 
-    // find the 3-loops where one starts with t
-    let mut triplets = HashSet::new();
-    for (a, a_neighbours) in &vertices {
-        if !a.starts_with('t') {
-            continue;
-        }
-        for b in a_neighbours {
-            if let Some(b_neighbours) = vertices.get(b) {
-                for c in b_neighbours {
-                    if c != a && vertices.get(c).unwrap().contains(a) {
-                        // a <-> b <-> c <-> a
-                        let mut triplet = [*a, *b, *c];
-                        triplet.sort();
-                        triplets.insert(triplet);
-                    }
-                }
-            }
+/// Describe the shape of the network
+pub fn day23report(input: &str, args: &[&str]) -> String {
+    if !args.is_empty() {
+        panic!("unexpected arguments {args:?}");
+    }
+    let graph = Graph::new(&parse_edges(input));
+    let names = |vertices: &[u32]| match vertices {
+        [] => "none".to_owned(),
+        _ => graph.join(vertices),
+    };
+
+    let mut out = format!(
+        "{} computers, {} connections\n",
+        graph.vertex_count(),
+        graph.edge_count()
+    );
+
+    let components = graph.components();
+    out += &format!(
+        "{} connected component(s) of size {}\n",
+        components.len(),
+        components.iter().map(|c| c.len()).join(", ")
+    );
+    out += &format!("diameter: {}\n", graph.diameter());
+
+    out += "degree distribution:\n";
+    for (degree, count) in graph.degree_distribution() {
+        out += &format!("  {degree:>3}: {count}\n");
+    }
+
+    out += &format!(
+        "articulation points: {}\n",
+        names(&graph.articulation_points())
+    );
+    let bridges = graph.bridges();
+    out += &format!(
+        "bridges: {}\n",
+        if bridges.is_empty() {
+            "none".to_owned()
+        } else {
+            bridges
+                .iter()
+                .map(|&(a, b)| format!("{}-{}", graph.name(a), graph.name(b)))
+                .join(",")
         }
+    );
+
+    let colouring = graph.greedy_colouring();
+    let colour_classes = (0..graph.vertex_count() as u32)
+        .into_group_map_by(|&v| colouring[v as usize])
+        .into_iter()
+        .sorted()
+        .collect_vec();
+    out += &format!("greedy colouring: {} colours\n", colour_classes.len());
+    for (colour, members) in colour_classes {
+        out += &format!("  {colour:>3}: {}\n", names(&members));
     }
+    out
+}
 
-    triplets.len()
+/// A set of vertex ids
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BitSet {
+    words: Vec<u64>,
 }
 
-pub fn day23part2(input: &str) -> String {
-    let edges = parse_edges(input);
-    let mut vertices: HashMap<ComputerName, Vec<ComputerName>> = Default::default();
+impl BitSet {
+    /// An empty set that can hold ids below `capacity`
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            words: vec![0; capacity.div_ceil(64)],
+        }
+    }
 
-    for &(a, b) in &edges {
-        let a_neighbours = vertices.entry(a).or_default();
-        a_neighbours.push(b);
-        a_neighbours.sort();
-        let b_neighbours = vertices.entry(b).or_default();
-        b_neighbours.push(a);
-        b_neighbours.sort();
+    pub fn insert(&mut self, id: u32) {
+        self.words[id as usize / 64] |= 1 << (id % 64);
     }
 
-    let mut sets = edges
-        .iter()
-        .map(|&(a, b)| if b > a { vec![a, b] } else { vec![b, a] })
-        .collect_vec();
+    pub fn remove(&mut self, id: u32) {
+        self.words[id as usize / 64] &= !(1 << (id % 64));
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        self.words[id as usize / 64] & (1 << (id % 64)) != 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&w| w == 0)
+    }
 
-    loop {
-        sets.sort();
-        sets.dedup();
-
-        // try to add each vertex to each set in turn, if it's connected to all members
-        let mut new_sets = vec![];
-        for (a, a_neighbours) in &vertices {
-            for set in &sets {
-                // are all members of the set my neighbours?
-                if sorted_superset(a_neighbours, set) {
-                    let mut new_set = set.clone();
-                    let i = new_set.partition_point(|n| n < a);
-                    new_set.insert(i, *a);
-                    new_sets.push(new_set);
+    /// The members, in increasing order
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, &word)| {
+            let mut word = word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
                 }
+                let bit = word.trailing_zeros();
+                word &= word - 1;
+                Some(i as u32 * 64 + bit)
+            })
+        })
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        self.zip_with(other, |a, b| a & b)
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        self.zip_with(other, |a, b| a & !b)
+    }
+
+    pub fn intersection_len(&self, other: &Self) -> usize {
+        self.words
+            .iter()
+            .zip(&other.words)
+            .map(|(a, b)| (a & b).count_ones() as usize)
+            .sum()
+    }
+
+    fn zip_with(&self, other: &Self, f: impl Fn(u64, u64) -> u64) -> Self {
+        Self {
+            words: self
+                .words
+                .iter()
+                .zip(&other.words)
+                .map(|(&a, &b)| f(a, b))
+                .collect(),
+        }
+    }
+}
+
+/// An undirected network of computers. Vertices are dense `u32` ids handed
+/// out in name order, so sorting ids also sorts names.
+#[derive(Debug, Clone)]
+struct Graph {
+    names: Vec<String>,
+    adjacency: Vec<BitSet>,
+}
+
+impl Graph {
+    pub fn new(edges: &[(&str, &str)]) -> Self {
+        let names = edges
+            .iter()
+            .flat_map(|&(a, b)| [a, b])
+            .sorted()
+            .dedup()
+            .collect_vec();
+        let ids: HashMap<&str, u32> = names
+            .iter()
+            .enumerate()
+            .map(|(id, &name)| (name, id as u32))
+            .collect();
+
+        let mut adjacency = vec![BitSet::new(names.len()); names.len()];
+        for (a, b) in edges {
+            let (a, b) = (ids[a], ids[b]);
+            if a != b {
+                adjacency[a as usize].insert(b);
+                adjacency[b as usize].insert(a);
             }
         }
-        if new_sets.is_empty() {
-            // cannot be improved
-            break;
-        } else {
-            sets = new_sets;
+        let names = names.into_iter().map(str::to_owned).collect();
+        Self { names, adjacency }
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.adjacency.iter().map(|n| n.len()).sum::<usize>() / 2
+    }
+
+    pub fn name(&self, id: u32) -> &str {
+        &self.names[id as usize]
+    }
+
+    /// The names of some vertices, comma-separated
+    pub fn join(&self, ids: &[u32]) -> String {
+        ids.iter().map(|&id| self.name(id)).join(",")
+    }
+
+    pub fn neighbours(&self, id: u32) -> &BitSet {
+        &self.adjacency[id as usize]
+    }
+
+    /// How many computers have each number of connections
+    pub fn degree_distribution(&self) -> BTreeMap<usize, usize> {
+        self.adjacency
+            .iter()
+            .map(|n| n.len())
+            .counts()
+            .into_iter()
+            .collect()
+    }
+
+    /// Sets of computers that can reach each other, each sorted, in order
+    pub fn components(&self) -> Vec<Vec<u32>> {
+        let mut seen = BitSet::new(self.vertex_count());
+        let mut components = vec![];
+        for start in 0..self.vertex_count() as u32 {
+            if seen.contains(start) {
+                continue;
+            }
+            let component = self
+                .distances_from(start)
+                .into_iter()
+                .positions(|d| d.is_some())
+                .map(|id| id as u32)
+                .collect_vec();
+            for &id in &component {
+                seen.insert(id);
+            }
+            components.push(component);
         }
+        components
     }
 
-    // implied by the question: there is only one answer
-    assert_eq!(sets.len(), 1);
+    /// Number of hops from `start` to every computer, if it can be reached
+    pub fn distances_from(&self, start: u32) -> Vec<Option<usize>> {
+        let mut distances = vec![None; self.vertex_count()];
+        distances[start as usize] = Some(0);
+        let mut queue = VecDeque::from([(start, 0)]);
+        while let Some((id, d)) = queue.pop_front() {
+            for next in self.neighbours(id).iter() {
+                if distances[next as usize].is_none() {
+                    distances[next as usize] = Some(d + 1);
+                    queue.push_back((next, d + 1));
+                }
+            }
+        }
+        distances
+    }
 
-    sets[0].iter().map(|n| format!("{}", n)).join(",")
-}
+    /// The longest shortest path between two computers that are connected
+    pub fn diameter(&self) -> usize {
+        (0..self.vertex_count() as u32)
+            .flat_map(|id| self.distances_from(id).into_iter().flatten().max())
+            .max()
+            .unwrap_or(0)
+    }
 
-fn sorted_superset<T: Ord>(greater: &[T], lesser: &[T]) -> bool {
-    let mut it1 = greater.iter();
-    'outer: for item in lesser {
-        'inner: for other in it1.by_ref() {
-            match other.cmp(item) {
-                Ordering::Equal => continue 'outer,
-                Ordering::Less => continue 'inner,
-                Ordering::Greater => return false,
+    /// Computers whose removal would split their component
+    pub fn articulation_points(&self) -> Vec<u32> {
+        self.low_links().0
+    }
+
+    /// Connections whose removal would split their component, each with the
+    /// smaller id first
+    pub fn bridges(&self) -> Vec<(u32, u32)> {
+        self.low_links().1
+    }
+
+    /// Tarjan's low-link DFS: articulation points and bridges, sorted
+    fn low_links(&self) -> (Vec<u32>, Vec<(u32, u32)>) {
+        struct State {
+            order: Vec<Option<usize>>,
+            low: Vec<usize>,
+            visited: usize,
+            cut_points: Vec<u32>,
+            bridges: Vec<(u32, u32)>,
+        }
+
+        fn visit(graph: &Graph, state: &mut State, id: u32, parent: Option<u32>) {
+            let index = state.visited;
+            state.visited += 1;
+            state.order[id as usize] = Some(index);
+            let mut low = index;
+            let mut children = 0;
+            for next in graph.neighbours(id).iter() {
+                if Some(next) == parent {
+                    continue;
+                }
+                if let Some(other) = state.order[next as usize] {
+                    // back edge
+                    low = low.min(other);
+                    continue;
+                }
+                children += 1;
+                visit(graph, state, next, Some(id));
+                let next_low = state.low[next as usize];
+                low = low.min(next_low);
+                if next_low > index {
+                    state.bridges.push((id.min(next), id.max(next)));
+                }
+                if parent.is_some() && next_low >= index {
+                    state.cut_points.push(id);
+                }
+            }
+            if parent.is_none() && children > 1 {
+                state.cut_points.push(id);
+            }
+            state.low[id as usize] = low;
+        }
+
+        let mut state = State {
+            order: vec![None; self.vertex_count()],
+            low: vec![0; self.vertex_count()],
+            visited: 0,
+            cut_points: vec![],
+            bridges: vec![],
+        };
+        for id in 0..self.vertex_count() as u32 {
+            if state.order[id as usize].is_none() {
+                visit(self, &mut state, id, None);
             }
         }
-        return false;
+        state.cut_points.sort();
+        state.cut_points.dedup();
+        state.bridges.sort();
+        (state.cut_points, state.bridges)
     }
-    true
-}
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-struct ComputerName {
-    name: u16,
-}
+    /// A colour number per vertex such that neighbours differ, handing out
+    /// the lowest free colour to the best-connected computers first
+    /// (Welsh–Powell)
+    pub fn greedy_colouring(&self) -> Vec<usize> {
+        let mut colours = vec![None; self.vertex_count()];
+        let order = (0..self.vertex_count() as u32)
+            .sorted_by_key(|&id| std::cmp::Reverse(self.neighbours(id).len()));
+        for id in order {
+            let taken: HashSet<usize> = self
+                .neighbours(id)
+                .iter()
+                .filter_map(|next| colours[next as usize])
+                .collect();
+            colours[id as usize] = (0..).find(|c| !taken.contains(c));
+        }
+        colours.into_iter().map(Option::unwrap).collect()
+    }
 
-impl FromStr for ComputerName {
-    type Err = ();
+    /// Number of sets of `k` fully connected computers where at least one
+    /// member passes `filter`
+    pub fn count_cliques(&self, k: usize, filter: impl Fn(u32) -> bool) -> usize {
+        fn extend(
+            later: &[BitSet],
+            k: usize,
+            filter: &impl Fn(u32) -> bool,
+            clique: &mut Vec<u32>,
+            candidates: &BitSet,
+        ) -> usize {
+            if clique.len() == k {
+                return clique.iter().any(|&id| filter(id)) as usize;
+            }
+            let mut count = 0;
+            for next in candidates.iter() {
+                clique.push(next);
+                count += extend(
+                    later,
+                    k,
+                    filter,
+                    clique,
+                    &candidates.intersection(&later[next as usize]),
+                );
+                clique.pop();
+            }
+            count
+        }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.len() != 2 {
-            Err(())
-        } else {
-            let bytes = s.as_bytes();
-            Ok(Self {
-                name: (bytes[0] as u16) << 8 | bytes[1] as u16,
-            })
+        if k == 0 {
+            return 0;
         }
+        // only ever add larger ids, so every clique is built in one order
+        let later = (0..self.vertex_count() as u32)
+            .map(|id| {
+                let mut set = BitSet::new(self.vertex_count());
+                for next in self.neighbours(id).iter().filter(|&next| next > id) {
+                    set.insert(next);
+                }
+                set
+            })
+            .collect_vec();
+        (0..self.vertex_count() as u32)
+            .map(|start| extend(&later, k, &filter, &mut vec![start], &later[start as usize]))
+            .sum()
     }
-}
 
-impl ComputerName {
-    pub fn starts_with(self, c: char) -> bool {
-        (self.name >> 8) as u8 as char == c
+    /// All cliques that cannot be extended, each sorted, in sorted order
+    pub fn maximal_cliques(&self) -> Vec<Vec<u32>> {
+        let mut cliques = vec![];
+        let mut all = BitSet::new(self.vertex_count());
+        for id in 0..self.vertex_count() as u32 {
+            all.insert(id);
+        }
+        self.bron_kerbosch(
+            &mut vec![],
+            all,
+            BitSet::new(self.vertex_count()),
+            &mut cliques,
+        );
+        cliques.sort();
+        cliques
     }
-}
 
-impl Display for ComputerName {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let bytes = [(self.name >> 8) as u8, (self.name & 0xff) as u8];
-        let s = str::from_utf8(&bytes).unwrap();
-        Display::fmt(s, f)
+    /// The largest cliques, each sorted, in sorted order
+    pub fn maximum_cliques(&self) -> Vec<Vec<u32>> {
+        let cliques = self.maximal_cliques();
+        let max_len = cliques.iter().map(|c| c.len()).max().unwrap_or(0);
+        cliques.into_iter().filter(|c| c.len() == max_len).collect()
     }
-}
 
-impl Debug for ComputerName {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "[{}]", self)
+    /// Bron–Kerbosch with pivoting: report every maximal clique that contains
+    /// all of `r`, some of `p` and none of `x`
+    fn bron_kerbosch(
+        &self,
+        r: &mut Vec<u32>,
+        mut p: BitSet,
+        mut x: BitSet,
+        cliques: &mut Vec<Vec<u32>>,
+    ) {
+        if p.is_empty() {
+            if x.is_empty() {
+                cliques.push(r.iter().copied().sorted().collect());
+            }
+            return;
+        }
+        // any maximal clique contains the pivot or one of its non-neighbours
+        let pivot = p
+            .iter()
+            .chain(x.iter())
+            .max_by_key(|&u| p.intersection_len(self.neighbours(u)))
+            .unwrap();
+        let candidates = p.difference(self.neighbours(pivot));
+
+        for v in candidates.iter() {
+            let neighbours = self.neighbours(v);
+            r.push(v);
+            self.bron_kerbosch(
+                r,
+                p.intersection(neighbours),
+                x.intersection(neighbours),
+                cliques,
+            );
+            r.pop();
+            p.remove(v);
+            x.insert(v);
+        }
     }
 }
 
-fn parse_edges(input: &str) -> Vec<(ComputerName, ComputerName)> {
+fn parse_edges(input: &str) -> Vec<(&str, &str)> {
     input
         .trim()
         .lines()
-        .map(|l| {
-            let (n1, n2) = l.split_once('-').unwrap();
-            (n1.parse().unwrap(), n2.parse().unwrap())
-        })
+        .map(|l| l.split_once('-').unwrap())
         .collect()
 }
 
@@ -198,6 +544,17 @@ mod test {
         td-yn\n\
     ";
 
+    // two triangles joined by one edge: two maximum cliques
+    static TWO_TRIANGLES: &str = "\
+        aa-ab\n\
+        ab-ac\n\
+        ac-aa\n\
+        ac-ba\n\
+        ba-bb\n\
+        bb-bc\n\
+        bc-ba\n\
+    ";
+
     #[test]
     fn part1test() {
         assert_eq!(day23part1(TEST_INPUT), 7);
@@ -209,9 +566,76 @@ mod test {
     }
 
     #[test]
-    fn superset_test() {
-        assert!(sorted_superset(&[1, 2, 3, 4], &[2, 4]));
-        assert!(!sorted_superset(&[1, 2, 3, 4], &[1, 5]));
-        assert!(!sorted_superset(&[1, 2, 4], &[2, 3]));
+    fn clique_test() {
+        let graph = Graph::new(&parse_edges(TEST_INPUT));
+        assert_eq!(graph.count_cliques(3, |_| true), 12);
+        assert_eq!(graph.count_cliques(4, |_| true), 1);
+        assert_eq!(
+            graph.count_cliques(3, |v| graph.name(v).starts_with("k")),
+            4
+        );
+
+        let maximal = graph.maximal_cliques();
+        assert_eq!(maximal.len(), 15);
+        assert_eq!(maximal.iter().filter(|c| c.len() == 3).count(), 8);
+
+        let graph = Graph::new(&parse_edges(TWO_TRIANGLES));
+        let maximum = graph.maximum_cliques();
+        let names = maximum.iter().map(|c| graph.join(c)).collect_vec();
+        assert_eq!(names, ["aa,ab,ac", "ba,bb,bc"]);
+        assert_eq!(day23part2(TWO_TRIANGLES), "aa,ab,ac");
+    }
+
+    #[test]
+    fn graph_test() {
+        // plus a separate pair
+        let graph = Graph::new(&parse_edges(&format!("{TWO_TRIANGLES}xa-xb\n")));
+
+        assert_eq!((graph.vertex_count(), graph.edge_count()), (8, 8));
+        let components = graph.components();
+        assert_eq!(
+            components.iter().map(|c| graph.join(c)).collect_vec(),
+            ["aa,ab,ac,ba,bb,bc", "xa,xb"]
+        );
+        assert_eq!(
+            graph.degree_distribution().into_iter().collect_vec(),
+            [(1, 2), (2, 4), (3, 2)]
+        );
+        assert_eq!(graph.diameter(), 3);
+        assert_eq!(graph.join(&graph.articulation_points()), "ac,ba");
+        let bridges = graph.bridges();
+        assert_eq!(
+            bridges.iter().map(|&(a, b)| graph.join(&[a, b])).join(" "),
+            "ac,ba xa,xb"
+        );
+
+        let colouring = graph.greedy_colouring();
+        assert_eq!(colouring.iter().max(), Some(&2));
+        for id in 0..graph.vertex_count() as u32 {
+            assert!(graph
+                .neighbours(id)
+                .iter()
+                .all(|next| colouring[next as usize] != colouring[id as usize]));
+        }
+    }
+
+    #[test]
+    fn long_names_test() {
+        let input = "\
+            router-server10\n\
+            server10-server2\n\
+            server2-router\n\
+            server2-printer.lan\n\
+        ";
+        assert_eq!(day23part2(input), "router,server10,server2");
+        let graph = Graph::new(&parse_edges(input));
+        assert_eq!(
+            graph.join(&[0, 1, 2, 3]),
+            "printer.lan,router,server10,server2"
+        );
+        assert_eq!(
+            graph.count_cliques(2, |v| graph.name(v).starts_with("serv")),
+            4
+        );
     }
 }
@@ -1,171 +1,212 @@
-use core::str;
-use std::{
-    collections::VecDeque,
-    fmt::{Debug, Display},
-    str::FromStr,
-};
+use std::{collections::VecDeque, fmt::Display, str::FromStr};
 
+use hashbrown::{HashMap, HashSet};
 use itertools::Itertools;
 
+use crate::bdd::{Bdd, BddRef, BinOp};
+
 pub fn day24part1(input: &str) -> u64 {
-    let (inputs, rules) = parse_input(input);
+    let (wires, inputs, rules) = parse_input(input);
 
-    let (x, y) = deserialize_inputs(&inputs);
+    let (x, y) = deserialize_inputs(&wires, &inputs);
 
-    elf_add(&rules, x, y)
+    Circuit::new(&wires, &rules).unwrap().evaluate(x, y)
 }
 
 pub fn day24part2(input: &str) -> String {
-    let (_, mut rules) = parse_input(input);
-
-    let mut swapped = vec![];
-    let mut frozen = vec![];
-
-    for i in 0..47 {
-        while let Some((a, b)) = fix_bit(&rules, i, &mut frozen) {
-            swap_rules(&mut rules, a, b);
-            swapped.push((a, b));
-        }
-    }
+    let (wires, _, mut rules) = parse_input(input);
+    let swapped = find_swaps(&wires, rules.clone(), |x, y| x.wrapping_add(y), 4)
+        .unwrap()
+        .expect("no repair found");
 
     let mut affected_outputs = vec![];
     for (a, b) in swapped {
+        swap_rules(&mut rules, a, b);
         affected_outputs.push(a);
         affected_outputs.push(b);
     }
+
+    // Make sure the repaired circuit really adds, for all inputs
+    let circuit = Circuit::new(&wires, &rules).expect("repaired circuit is malformed");
+    if let Err(counterexample) = prove_adder(&circuit) {
+        panic!("repaired circuit is still wrong: {counterexample}");
+    }
     affected_outputs.sort();
 
-    affected_outputs.iter().map(|n| format!("{}", n)).join(",")
+    affected_outputs.iter().map(|&n| wires.name(n)).join(",")
 }
 
-fn fix_bit(rules: &[Rule], bit: u64, frozen: &mut Vec<Node>) -> Option<(Node, Node)> {
-    let n_possible_nodes = Node::max().idx() + 1;
-
-    // figure out what influences the bit we're trying to fix
+/// Write the circuit as a Graphviz graph, highlighting the wires that part 2
+/// would swap (unless `--no-repair` is given)
+pub fn day24dot(input: &str, args: &[&str]) -> String {
+    let (wires, _, rules) = parse_input(input);
+    let suspects = if args.contains(&"--no-repair") {
+        vec![]
+    } else {
+        find_swaps(&wires, rules.clone(), |x, y| x.wrapping_add(y), 4)
+            .ok()
+            .flatten()
+            .into_iter()
+            .flatten()
+            .flat_map(|(a, b)| [a, b])
+            .collect()
+    };
+    to_dot(&wires, &rules, &suspects)
+}
 
-    let mut direct_orgin_map = vec![vec![]; n_possible_nodes];
-    let mut rules_by_output = vec![vec![]; n_possible_nodes];
-    for (rule_idx, rule) in rules.iter().enumerate() {
-        rules_by_output[rule.output.idx()].push(rule_idx);
-        direct_orgin_map[rule.output.idx()].push(rule.inputs.0);
-        direct_orgin_map[rule.output.idx()].push(rule.inputs.1);
-        direct_orgin_map[rule.output.idx()].sort();
-        direct_orgin_map[rule.output.idx()].dedup();
+/// Find the swaps needed to make the circuit compute something other than a
+/// sum: `--target add|and|or|xor|lt|eq` (default add), `--max-swaps N`
+/// (default 4)
+pub fn day24repair(input: &str, args: &[&str]) -> String {
+    let (wires, _, rules) = parse_input(input);
+    let mut target = "add";
+    let mut max_swaps = 4;
+    for (option, value) in crate::option_pairs(args) {
+        match option {
+            "--target" => target = value,
+            "--max-swaps" => max_swaps = value.parse().unwrap(),
+            _ => panic!("unknown option {option}"),
+        }
     }
+    let target = target_by_name(target).expect("unknown target");
 
-    if direct_orgin_map[Node::z(bit as u16).idx()].is_empty() {
-        return None;
+    match find_swaps(&wires, rules, target, max_swaps) {
+        Ok(Some(swaps)) => {
+            let mut out = String::new();
+            for &(a, b) in &swaps {
+                out += &format!("swap {} <-> {}\n", wires.name(a), wires.name(b));
+            }
+            let swapped = swaps.iter().flat_map(|&(a, b)| [a, b]).sorted();
+            out + &swapped.map(|n| wires.name(n)).join(",") + "\n"
+        }
+        Ok(None) => format!("no repair with at most {max_swaps} swaps\n"),
+        Err(TooWide(width)) => format!(
+            "can't test {} x, {} y and {} z bits: at most 64 of each fit\n",
+            width.x, width.y, width.z
+        ),
     }
+}
 
-    let mut origins = vec![];
-    let mut relevant_rules = vec![];
-    {
-        let mut node_queue = VecDeque::new();
-        node_queue.push_back(Node::z(bit as u16));
-        while let Some(node) = node_queue.pop_front() {
-            let new_origins = &direct_orgin_map[node.idx()];
-            node_queue.extend(new_origins.iter().copied());
-            origins.extend_from_slice(new_origins);
-        }
-        origins.sort();
-        origins.dedup();
-
-        let mut rule_queue = VecDeque::new();
-        rule_queue.push_back(Node::z(bit as u16));
-        while let Some(output) = rule_queue.pop_front() {
-            let rule_indices = &rules_by_output[output.idx()];
-            for &rule_idx in rule_indices {
-                let rule = rules[rule_idx];
-                rule_queue.push_back(rule.inputs.0);
-                rule_queue.push_back(rule.inputs.1);
-            }
-            relevant_rules.extend_from_slice(rule_indices);
+/// Write the circuit as a structural Verilog module
+pub fn day24verilog(input: &str, _args: &[&str]) -> String {
+    let (wires, _, rules) = parse_input(input);
+    to_verilog(&wires, &rules)
+}
+
+/// Compare the circuit to the textbook ripple-carry adder, listing what looks
+/// wrong for every bit along with the swaps that part 2 would make
+pub fn day24check(input: &str, _args: &[&str]) -> String {
+    let (wires, _, mut rules) = parse_input(input);
+    let mut out = String::new();
+
+    let violations = check_structure(&wires, &rules);
+    for (bit, group) in &violations.iter().chunk_by(|v| v.bit) {
+        out += &format!("bit {bit}:\n");
+        for violation in group {
+            out += &format!("  {violation}\n");
         }
     }
 
-    if test_bit_rules(rules, bit) {
-        frozen.extend(relevant_rules.iter().map(|&i| rules[i].output));
-        frozen.sort();
-        return None;
+    out += "repair plan:\n";
+    let swaps = find_swaps(&wires, rules.clone(), |x, y| x.wrapping_add(y), 4);
+    for (a, b) in swaps.ok().flatten().unwrap_or_default() {
+        let fixes = violations
+            .iter()
+            .filter(|v| v.wire == a || v.wire == b)
+            .count();
+        out += &format!(
+            "  swap {} <-> {} (explains {fixes} of the above)\n",
+            wires.name(a),
+            wires.name(b)
+        );
+        swap_rules(&mut rules, a, b);
     }
+    let remaining = check_structure(&wires, &rules).len();
+    out += &format!("violations left after repair: {remaining}\n");
+    out
+}
 
-    let expected_x_bits = (0..=bit).map(|i| Node::x(i as u16)).collect_vec();
-    let expected_y_bits = (0..=bit).map(|i| Node::y(i as u16)).collect_vec();
-    let illegal_x_bits = ((bit + 1)..=45).map(|i| Node::x(i as u16)).collect_vec();
-    let illegal_y_bits = ((bit + 1)..=45).map(|i| Node::y(i as u16)).collect_vec();
+/// Find at most `max_swaps` pairs of gate outputs to swap so that the circuit
+/// computes `target`, fixing the lowest wrong z bit first
+fn find_swaps(
+    wires: &Wires,
+    mut rules: Vec<Rule>,
+    target: Target,
+    max_swaps: usize,
+) -> Result<Option<Vec<(Node, Node)>>, TooWide> {
+    let tests = TestSuite::new(Width::of(wires, &rules)?, target);
+    let mut swaps = vec![];
+    Ok(search_swaps(wires, &mut rules, &tests, max_swaps, &mut swaps).then_some(swaps))
+}
 
-    let mut input_rule_map: Vec<Vec<&Rule>> = vec![];
-    input_rule_map.resize_with(n_possible_nodes, Default::default);
-    for rule in rules {
-        input_rule_map[rule.inputs.0.idx()].push(rule);
-        input_rule_map[rule.inputs.1.idx()].push(rule);
+fn search_swaps(
+    wires: &Wires,
+    rules: &mut [Rule],
+    tests: &TestSuite,
+    swaps_left: usize,
+    swaps: &mut Vec<(Node, Node)>,
+) -> bool {
+    let Ok(circuit) = Circuit::new(wires, rules) else {
+        return false;
+    };
+    let Some(wrong_bit) = tests.first_wrong_bit(&circuit) else {
+        return true;
+    };
+    if swaps_left == 0 {
+        return false;
     }
 
-    let nodes_with_plausible_inputs = {
-        let mut inputs_are_right = vec![false; n_possible_nodes];
-        let mut queue = VecDeque::new();
-        queue.extend(expected_x_bits.iter().copied());
-        queue.extend(expected_y_bits.iter().copied());
+    // Anything the lower (correct) bits depend on had better stay where it
+    // is, and the swap has to change something the wrong bit depends on.
+    let drivers: HashMap<_, _> = rules.iter().map(|rule| (rule.output, *rule)).collect();
+    let cone = |bit: u16| {
+        let mut cone = HashSet::new();
+        let mut queue = VecDeque::from_iter(wires.z(bit));
         while let Some(node) = queue.pop_front() {
-            if frozen.binary_search(&node).is_err() {
-                if !node.is_x() && !node.is_y() {
-                    inputs_are_right[node.idx()] = true;
-                }
-                for rule in &input_rule_map[node.idx()] {
-                    if !queue.contains(&rule.output) {
-                        queue.push_back(rule.output);
-                    }
+            if let Some(rule) = drivers.get(&node) {
+                if cone.insert(node) {
+                    queue.extend([rule.inputs.0, rule.inputs.1]);
                 }
             }
         }
-
-        queue.extend(illegal_x_bits.iter().copied());
-        queue.extend(illegal_y_bits.iter().copied());
-        while let Some(node) = queue.pop_front() {
-            if frozen.binary_search(&node).is_err() {
-                if !node.is_x() && !node.is_y() {
-                    inputs_are_right[node.idx()] = false;
-                }
-                for rule in &input_rule_map[node.idx()] {
-                    if !queue.contains(&rule.output) {
-                        queue.push_back(rule.output);
-                    }
-                }
-            }
-        }
-
-        inputs_are_right
-            .iter()
-            .enumerate()
-            .filter_map(|(i, is_usable)| {
-                if *is_usable {
-                    Some(Node(i as u16))
-                } else {
-                    None
-                }
-            })
-            .collect_vec()
+        cone
     };
-
-    let nodes_leading_to_output = relevant_rules
+    let settled: HashSet<_> = (0..wrong_bit).flat_map(cone).collect();
+    let suspects = cone(wrong_bit)
+        .into_iter()
+        .filter(|node| !settled.contains(node))
+        .sorted()
+        .collect_vec();
+    let candidates = rules
         .iter()
-        .map(|r| rules[*r].output)
+        .map(|rule| rule.output)
+        .filter(|node| !settled.contains(node))
+        .sorted()
         .collect_vec();
 
-    for &node1 in &nodes_with_plausible_inputs {
-        for &node2 in &nodes_leading_to_output {
-            if node1 != node2 {
-                // Try swapping!
-                let mut new_rules = rules.to_vec();
-                swap_rules(&mut new_rules, node1, node2);
-                if test_bit_rules(&new_rules, bit) {
-                    return Some((node1, node2));
+    for &a in &suspects {
+        for &b in &candidates {
+            if a == b || (b < a && suspects.contains(&b)) {
+                continue;
+            }
+            swap_rules(rules, a, b);
+            let improved = Circuit::new(wires, rules).is_ok_and(|circuit| {
+                tests
+                    .first_wrong_bit(&circuit)
+                    .is_none_or(|bit| bit > wrong_bit)
+            });
+            if improved {
+                swaps.push((a, b));
+                if search_swaps(wires, rules, tests, swaps_left - 1, swaps) {
+                    return true;
                 }
+                swaps.pop();
             }
+            swap_rules(rules, a, b);
         }
     }
 
-    panic!("Failed to find solution");
+    false
 }
 
 fn swap_rules(rules: &mut [Rule], a: Node, b: Node) {
@@ -178,148 +219,727 @@ fn swap_rules(rules: &mut [Rule], a: Node, b: Node) {
     }
 }
 
-fn test_bit_rules(rules: &[Rule], bit: u64) -> bool {
-    let mut assertions = vec![];
-    if bit <= 44 {
-        assertions.push((1 << bit, 1 << bit, false));
-        assertions.push((1 << bit, 0 << bit, true));
-        assertions.push((0 << bit, 1 << bit, true));
-        assertions.push((0 << bit, 0 << bit, false));
-        if bit > 0 {
-            assertions.push((0b01 << (bit - 1), 0b00 << (bit - 1), false));
-            assertions.push((0b00 << (bit - 1), 0b01 << (bit - 1), false));
-            assertions.push((0b01 << (bit - 1), 0b01 << (bit - 1), true));
-            assertions.push((0b10 << (bit - 1), 0b01 << (bit - 1), true));
-            assertions.push((0b01 << (bit - 1), 0b10 << (bit - 1), true));
-            assertions.push((0b11 << (bit - 1), 0b00 << (bit - 1), true));
-            assertions.push((0b00 << (bit - 1), 0b11 << (bit - 1), true));
-            assertions.push((0b11 << (bit - 1), 0b01 << (bit - 1), false));
-            assertions.push((0b01 << (bit - 1), 0b11 << (bit - 1), false));
-            assertions.push((0b11 << (bit - 1), 0b10 << (bit - 1), false));
-            assertions.push((0b10 << (bit - 1), 0b11 << (bit - 1), false));
-            assertions.push((0b11 << (bit - 1), 0b11 << (bit - 1), true));
+/// Get x and y from inputs (ignoring any bits past 64, which no [Circuit]
+/// can have)
+fn deserialize_inputs(wires: &Wires, inputs: &[(Node, bool)]) -> (u64, u64) {
+    let mut x = 0;
+    let mut y = 0;
+
+    for &(n, val) in inputs {
+        let bit_val = |bit: u16| (val as u64).checked_shl(bit as u32).unwrap_or(0);
+        if let Some(bit) = wires.x_bit(n) {
+            x |= bit_val(bit);
+        } else if let Some(bit) = wires.y_bit(n) {
+            y |= bit_val(bit);
         }
-    } else {
-        assertions.push((0b01 << (bit - 1), 0b00 << (bit - 1), false));
-        assertions.push((0b00 << (bit - 1), 0b01 << (bit - 1), false));
-        assertions.push((0b01 << (bit - 1), 0b01 << (bit - 1), true));
     }
 
-    for (x, y, ans) in assertions {
-        let z = elf_add(rules, x, y);
-        let bit_set = (z & (1 << bit)) != 0;
-        if ans != bit_set {
-            return false;
+    (x, y)
+}
+
+/// A gate-level simulation of a set of rules, with the gates sorted so that
+/// every gate comes after the gates driving its inputs.
+///
+/// Every wire gets a slot: first the x bits, then the y bits, then the output
+/// of each gate in order.
+#[derive(Debug, Clone)]
+struct Circuit {
+    width: Width,
+    gates: Vec<Gate>,
+    z_slots: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Gate {
+    inputs: (usize, usize),
+    operation: Operation,
+}
+
+/// What is wrong with a set of rules, naming the wires involved
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CircuitError {
+    /// These wires depend on each other in a loop (or on such a loop)
+    Cycle(Vec<String>),
+    /// This wire is used, but no gate drives it
+    Undriven(String),
+    /// More than one gate drives this wire (or it is also an input)
+    MultipleDrivers(String),
+    /// There are more than 64 x, y or z wires
+    TooWide(Width),
+}
+
+impl From<TooWide> for CircuitError {
+    fn from(TooWide(width): TooWide) -> Self {
+        Self::TooWide(width)
+    }
+}
+
+impl Circuit {
+    pub fn new(wires: &Wires, rules: &[Rule]) -> Result<Self, CircuitError> {
+        let width = Width::of(wires, rules)?;
+        let named = |node| wires.name(node).to_string();
+
+        let mut drivers = vec![None; wires.len()];
+        for (rule_idx, rule) in rules.iter().enumerate() {
+            if let Some(('x' | 'y', _)) = wires.numbered(rule.output) {
+                return Err(CircuitError::MultipleDrivers(named(rule.output)));
+            }
+            if drivers[rule.output.idx()].replace(rule_idx).is_some() {
+                return Err(CircuitError::MultipleDrivers(named(rule.output)));
+            }
+        }
+
+        let mut slots = vec![None; wires.len()];
+        for rule in rules {
+            for input in [rule.inputs.0, rule.inputs.1] {
+                match wires.numbered(input) {
+                    Some(('x', bit)) => slots[input.idx()] = Some(bit as usize),
+                    Some(('y', bit)) => slots[input.idx()] = Some((width.x + bit) as usize),
+                    _ => (),
+                };
+            }
+        }
+        let n_inputs = (width.x + width.y) as usize;
+
+        // Kahn's algorithm: a gate is ready once all its inputs have slots
+        let mut consumers = vec![vec![]; wires.len()];
+        let mut missing_inputs = vec![0; rules.len()];
+        for (rule_idx, rule) in rules.iter().enumerate() {
+            for input in [rule.inputs.0, rule.inputs.1] {
+                if drivers[input.idx()].is_some() {
+                    consumers[input.idx()].push(rule_idx);
+                    missing_inputs[rule_idx] += 1;
+                } else if slots[input.idx()].is_none() {
+                    return Err(CircuitError::Undriven(named(input)));
+                }
+            }
+        }
+
+        let mut queue = (0..rules.len())
+            .filter(|&i| missing_inputs[i] == 0)
+            .collect::<VecDeque<_>>();
+        let mut order = Vec::with_capacity(rules.len());
+        let mut z_slots = vec![None; width.z as usize];
+        while let Some(rule_idx) = queue.pop_front() {
+            let output = rules[rule_idx].output;
+            if let Some(bit) = wires.z_bit(output) {
+                z_slots[bit as usize] = Some(n_inputs + order.len());
+            }
+            slots[output.idx()] = Some(n_inputs + order.len());
+            order.push(rule_idx);
+            for &consumer in &consumers[output.idx()] {
+                missing_inputs[consumer] -= 1;
+                if missing_inputs[consumer] == 0 {
+                    queue.push_back(consumer);
+                }
+            }
+        }
+
+        if order.len() < rules.len() {
+            let stuck = (0..rules.len())
+                .filter(|&i| missing_inputs[i] != 0)
+                .map(|i| rules[i].output)
+                .sorted()
+                .map(named)
+                .collect();
+            return Err(CircuitError::Cycle(stuck));
+        }
+        let z_slots = z_slots
+            .into_iter()
+            .enumerate()
+            .map(|(bit, slot)| {
+                // z wires below the highest one always have a name, see [Wires::new]
+                slot.ok_or_else(|| CircuitError::Undriven(Wires::name_for_bit('z', bit as u16)))
+            })
+            .collect::<Result<_, _>>()?;
+
+        let gates = order
+            .into_iter()
+            .map(|rule_idx| {
+                let rule = &rules[rule_idx];
+                Gate {
+                    inputs: (
+                        slots[rule.inputs.0.idx()].unwrap(),
+                        slots[rule.inputs.1.idx()].unwrap(),
+                    ),
+                    operation: rule.operation,
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            width,
+            gates,
+            z_slots,
+        })
+    }
+
+    /// Evaluate the circuit over any kind of value, given one value per x and
+    /// y bit (missing bits count as `zero`) and a way to apply each gate
+    pub fn evaluate_with<T: Copy>(
+        &self,
+        x_bits: &[T],
+        y_bits: &[T],
+        zero: T,
+        mut apply: impl FnMut(Operation, T, T) -> T,
+    ) -> Vec<T> {
+        let width = self.width;
+        let mut vals = Vec::with_capacity((width.x + width.y) as usize + self.gates.len());
+        vals.extend((0..width.x as usize).map(|bit| x_bits.get(bit).copied().unwrap_or(zero)));
+        vals.extend((0..width.y as usize).map(|bit| y_bits.get(bit).copied().unwrap_or(zero)));
+        for gate in &self.gates {
+            let val = apply(gate.operation, vals[gate.inputs.0], vals[gate.inputs.1]);
+            vals.push(val);
+        }
+        self.z_slots.iter().map(|&slot| vals[slot]).collect()
+    }
+
+    /// Evaluate 64 assignments at once: bit `i` of `x_lanes[n]` is bit `n`
+    /// of x in the `i`th assignment, and likewise for y and the result z.
+    pub fn evaluate_lanes(&self, x_lanes: &[u64], y_lanes: &[u64]) -> Vec<u64> {
+        self.evaluate_with(x_lanes, y_lanes, 0, Operation::apply_lanes)
+    }
+
+    /// Evaluate the circuit for every (x, y) pair, 64 pairs at a time
+    pub fn evaluate_many(&self, assignments: &[(u64, u64)]) -> Vec<u64> {
+        let mut results = Vec::with_capacity(assignments.len());
+        for chunk in assignments.chunks(64) {
+            let x_lanes = to_lanes(chunk.iter().map(|&(x, _)| x), self.width.x);
+            let y_lanes = to_lanes(chunk.iter().map(|&(_, y)| y), self.width.y);
+            let z_lanes = self.evaluate_lanes(&x_lanes, &y_lanes);
+            results.extend((0..chunk.len()).map(|lane| {
+                z_lanes
+                    .iter()
+                    .enumerate()
+                    .fold(0, |z, (bit, z_lane)| z | ((z_lane >> lane) & 1) << bit)
+            }));
         }
+        results
     }
 
-    true
+    pub fn evaluate(&self, x: u64, y: u64) -> u64 {
+        self.evaluate_many(&[(x, y)])[0]
+    }
 }
 
-/// Get x and y from inputs
-fn deserialize_inputs(inputs: &[(Node, bool)]) -> (u64, u64) {
-    let mut x = 0;
-    let mut y = 0;
-    // let min_x = 33 * 1296;
-    let min_y = 34 * 1296;
-    let mut x_bit = 0;
-    let mut y_bit = 0;
-
-    for (n, val) in inputs {
-        if n.0 >= min_y {
-            y |= (*val as u64) << y_bit;
-            y_bit += 1;
-        } else {
-            x |= (*val as u64) << x_bit;
-            x_bit += 1;
+/// Transpose up to 64 values of `n_bits` bits each, so that bit `i` of
+/// element `n` of the result is bit `n` of the `i`th value
+fn to_lanes(values: impl Iterator<Item = u64>, n_bits: u16) -> Vec<u64> {
+    let mut lanes = vec![0; n_bits as usize];
+    for (lane, value) in values.enumerate() {
+        for (bit, bit_lane) in lanes.iter_mut().enumerate() {
+            *bit_lane |= ((value >> bit) & 1) << lane;
         }
     }
+    lanes
+}
 
-    (x, y)
+/// A function of x and y that a circuit is meant to compute
+type Target = fn(u64, u64) -> u64;
+
+/// Known target functions, for the `repair` tool
+fn target_by_name(name: &str) -> Option<Target> {
+    match name {
+        "add" => Some(|x, y| x.wrapping_add(y)),
+        "and" => Some(|x, y| x & y),
+        "or" => Some(|x, y| x | y),
+        "xor" => Some(|x, y| x ^ y),
+        "lt" => Some(|x, y| (x < y) as u64),
+        "eq" => Some(|x, y| (x == y) as u64),
+        _ => None,
+    }
 }
 
-fn elf_add(rules: &[Rule], x: u64, y: u64) -> u64 {
-    let n_possible_nodes = Node::max().idx() + 1;
+/// Test vectors for a target function, already split into 64-lane chunks
+/// for [Circuit::evaluate_lanes]
+struct TestSuite {
+    chunks: Vec<TestChunk>,
+}
+
+struct TestChunk {
+    x_lanes: Vec<u64>,
+    y_lanes: Vec<u64>,
+    z_lanes: Vec<u64>,
+}
 
-    let mut input_rule_map: Vec<Vec<&Rule>> = vec![];
-    input_rule_map.resize_with(n_possible_nodes, Default::default);
+impl TestSuite {
+    pub fn new(width: Width, target: Target) -> Self {
+        let mut assignments = vec![];
+
+        // every combination of neighbouring bits of x and y: this is what it
+        // takes to exercise each carry of an adder
+        for bit in 0..width.x.max(width.y) {
+            let low_bit = bit.saturating_sub(1);
+            for (x_bits, y_bits) in (0..4).cartesian_product(0..4) {
+                let x = (x_bits << low_bit) & width.x_mask();
+                let y = (y_bits << low_bit) & width.y_mask();
+                assignments.push((x, y));
+            }
+        }
+
+        // and some arbitrary ones for everything else (xorshift)
+        let mut state = 0x2545f4914f6cdd1d_u64;
+        let mut random = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..256 {
+            assignments.push((random() & width.x_mask(), random() & width.y_mask()));
+        }
+
+        let chunks = assignments
+            .chunks(64)
+            .map(|chunk| TestChunk {
+                x_lanes: to_lanes(chunk.iter().map(|&(x, _)| x), width.x),
+                y_lanes: to_lanes(chunk.iter().map(|&(_, y)| y), width.y),
+                z_lanes: to_lanes(chunk.iter().map(|&(x, y)| target(x, y)), width.z),
+            })
+            .collect();
+        Self { chunks }
+    }
+
+    /// The lowest z bit that is wrong for some test vector
+    pub fn first_wrong_bit(&self, circuit: &Circuit) -> Option<u16> {
+        let mut wrong = vec![0; circuit.width.z as usize];
+        for chunk in &self.chunks {
+            let z_lanes = circuit.evaluate_lanes(&chunk.x_lanes, &chunk.y_lanes);
+            for ((wrong, actual), expected) in wrong.iter_mut().zip(z_lanes).zip(&chunk.z_lanes) {
+                *wrong |= actual ^ expected;
+            }
+        }
+        wrong.iter().position(|&w| w != 0).map(|bit| bit as u16)
+    }
+}
+
+/// For every wire, the highest input bit it depends on: in a ripple-carry
+/// adder, that is the bit slice the wire belongs to
+fn bit_slices(wires: &Wires, rules: &[Rule]) -> HashMap<Node, u16> {
+    fn slice_of(
+        wires: &Wires,
+        node: Node,
+        drivers: &HashMap<Node, &Rule>,
+        slices: &mut HashMap<Node, u16>,
+    ) -> u16 {
+        if let Some(&slice) = slices.get(&node) {
+            return slice;
+        }
+        // guard against cycles (the answer for them is meaningless anyway)
+        slices.insert(node, 0);
+        let slice =
+            match (wires.x_bit(node).or(wires.y_bit(node)), drivers.get(&node)) {
+                (Some(bit), _) => bit,
+                (None, Some(rule)) => slice_of(wires, rule.inputs.0, drivers, slices)
+                    .max(slice_of(wires, rule.inputs.1, drivers, slices)),
+                (None, None) => 0,
+            };
+        slices.insert(node, slice);
+        slice
+    }
+
+    let drivers = rules.iter().map(|rule| (rule.output, rule)).collect();
+    let mut slices = HashMap::new();
     for rule in rules {
-        input_rule_map[rule.inputs.0.idx()].push(rule);
-        input_rule_map[rule.inputs.1.idx()].push(rule);
-    }
-
-    let mut vals = vec![None; n_possible_nodes];
-    let mut queue = VecDeque::new();
-
-    // input the values
-    for bit in 0..45 {
-        let val = x & (1 << bit) != 0;
-        let node = Node::x(bit);
-        queue.push_back((node, val));
-    }
-    for bit in 0..45 {
-        let val = y & (1 << bit) != 0;
-        let node = Node::y(bit);
-        queue.push_back((node, val));
-    }
-
-    while let Some((node, val)) = queue.pop_front() {
-        if vals[node.idx()].is_none() {
-            vals[node.idx()] = Some(val);
-            for rule in &input_rule_map[node.idx()] {
-                if let (Some(lhs), Some(rhs), None) = (
-                    vals[rule.inputs.0.idx()],
-                    vals[rule.inputs.1.idx()],
-                    vals[rule.output.idx()],
-                ) {
-                    queue.push_back((rule.output, rule.operation.apply(lhs, rhs)));
+        slice_of(wires, rule.output, &drivers, &mut slices);
+    }
+    slices
+}
+
+/// Inputs for which a circuit doesn't add up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Counterexample {
+    x: u64,
+    y: u64,
+    bit: u16,
+}
+
+impl Display for Counterexample {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "z{:02} is wrong for x={}, y={}",
+            self.bit, self.x, self.y
+        )
+    }
+}
+
+/// Prove that every z output is the corresponding bit of x + y, by building
+/// a BDD for each one and comparing it to the BDD of a known-good adder.
+///
+/// The variables are x0, y0, x1, y1, ... in that order, which keeps the BDDs
+/// of an adder small.
+fn prove_adder(circuit: &Circuit) -> Result<(), Counterexample> {
+    let width = circuit.width;
+    let mut bdd = Bdd::new();
+    let xs = (0..width.x as u32).map(|i| bdd.var(2 * i)).collect_vec();
+    let ys = (0..width.y as u32)
+        .map(|i| bdd.var(2 * i + 1))
+        .collect_vec();
+    let zs = circuit.evaluate_with(&xs, &ys, BddRef::FALSE, |op, a, b| {
+        bdd.apply(op.into(), a, b)
+    });
+
+    let mut carry = BddRef::FALSE;
+    for (bit, &z) in zs.iter().enumerate() {
+        let x = xs.get(bit).copied().unwrap_or(BddRef::FALSE);
+        let y = ys.get(bit).copied().unwrap_or(BddRef::FALSE);
+        let half_sum = bdd.xor(x, y);
+        let sum = bdd.xor(half_sum, carry);
+        let carry1 = bdd.and(x, y);
+        let carry2 = bdd.and(half_sum, carry);
+        carry = bdd.or(carry1, carry2);
+
+        let difference = bdd.xor(sum, z);
+        if let Some(assignment) = bdd.satisfying_assignment(difference) {
+            let (mut x, mut y) = (0, 0);
+            for (var, value) in assignment {
+                if var % 2 == 0 {
+                    x |= (value as u64) << (var / 2);
+                } else {
+                    y |= (value as u64) << (var / 2);
                 }
             }
+            return Err(Counterexample {
+                x,
+                y,
+                bit: bit as u16,
+            });
         }
     }
 
-    // Get the z's
-    let first_z = 35 * 1296;
-    let mut z = 0;
-    for val in vals[first_z..].iter().rev() {
-        if let &Some(bit) = val {
-            z <<= 1;
-            z |= bit as u64;
+    Ok(())
+}
+
+/// A gate that doesn't fit the shape of a ripple-carry adder
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Violation {
+    bit: u16,
+    wire: Node,
+    /// The gate as it is, e.g. `z01 = s02 XOR c01 (feeding nothing)`
+    found: String,
+    expected: String,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: expected {}", self.found, self.expected)
+    }
+}
+
+/// Check every gate against its role in a ripple-carry adder, where for bit i
+///
+///   s_i = x_i XOR y_i      (half sum)
+///   z_i = s_i XOR c_i-1
+///   c_i = (x_i AND y_i) OR (s_i AND c_i-1)
+///
+/// except that bit 0 has no carry in, and the last z is the last carry.
+/// Violations come out sorted by bit.
+fn check_structure(wires: &Wires, rules: &[Rule]) -> Vec<Violation> {
+    use Operation::*;
+
+    let width = Width::count(wires, rules);
+    let slices = bit_slices(wires, rules);
+    let carry_out = (width.z > width.x).then(|| wires.z(width.z - 1)).flatten();
+    let drivers: HashMap<_, _> = rules.iter().map(|rule| (rule.output, rule)).collect();
+    let mut consumers: HashMap<Node, Vec<Operation>> = HashMap::new();
+    for rule in rules {
+        consumers
+            .entry(rule.inputs.0)
+            .or_default()
+            .push(rule.operation);
+        consumers
+            .entry(rule.inputs.1)
+            .or_default()
+            .push(rule.operation);
+    }
+
+    // if the gate combines x_i and y_i, which i
+    let input_bit = |rule: &Rule| {
+        let (a, b) = rule.inputs;
+        let input_bit = |node| wires.x_bit(node).or(wires.y_bit(node));
+        match (input_bit(a), input_bit(b)) {
+            (Some(i), Some(j)) if i == j && wires.is_x(a) != wires.is_x(b) => Some(i),
+            _ => None,
+        }
+    };
+
+    let mut violations = vec![];
+    for rule in rules {
+        let wire = rule.output;
+        let bit = input_bit(rule)
+            .or(wires.z_bit(wire))
+            .unwrap_or(slices[&wire]);
+        let uses = consumers
+            .get(&wire)
+            .map(|ops| ops.iter().copied().sorted().collect_vec())
+            .unwrap_or_default();
+        let feeds = |ops: &[Operation]| uses == ops;
+        let found = format!(
+            "{} = {} {} {} (feeding {})",
+            wires.name(wire),
+            wires.name(rule.inputs.0),
+            rule.operation,
+            wires.name(rule.inputs.1),
+            if uses.is_empty() {
+                "nothing".to_string()
+            } else {
+                uses.iter().join(", ")
+            }
+        );
+        let mut report = |expected: String| {
+            violations.push(Violation {
+                bit,
+                wire,
+                found: found.clone(),
+                expected,
+            })
+        };
+
+        if Some(wire) == carry_out {
+            if rule.operation != Or && width.x > 1 {
+                report("the OR of the two carry terms".to_string());
+            }
+            continue;
+        }
+        if wires.z_bit(wire).is_some() && rule.operation != Xor {
+            report("an XOR, like every sum bit".to_string());
+            continue;
+        }
+
+        match (rule.operation, input_bit(rule)) {
+            (Xor, Some(0)) => {
+                if Some(wire) != wires.z(0) {
+                    report("to drive z00 directly".to_string());
+                }
+            }
+            (Xor, Some(_)) => {
+                if !feeds(&[And, Xor]) {
+                    report("a half sum, feeding one AND and one XOR".to_string());
+                }
+            }
+            (Xor, None) => match wires.z_bit(wire) {
+                None => report("to drive a z wire".to_string()),
+                Some(i) => {
+                    let has_half_sum = [rule.inputs.0, rule.inputs.1].iter().any(|input| {
+                        drivers
+                            .get(input)
+                            .is_some_and(|r| r.operation == Xor && input_bit(r) == Some(i))
+                    });
+                    if !has_half_sum {
+                        report(format!(
+                            "one operand to be {} XOR {}",
+                            Wires::name_for_bit('x', i),
+                            Wires::name_for_bit('y', i)
+                        ));
+                    }
+                }
+            },
+            (And, Some(0)) => {
+                if !feeds(&[And, Xor]) {
+                    report("the first carry, feeding one AND and one XOR".to_string());
+                }
+            }
+            (And, _) => {
+                if !feeds(&[Or]) {
+                    report("a carry term, feeding an OR".to_string());
+                }
+            }
+            (Or, Some(_)) => report("no OR gate directly on the inputs".to_string()),
+            (Or, None) => {
+                if !feeds(&[And, Xor]) {
+                    report("a carry, feeding one AND and one XOR".to_string());
+                }
+            }
         }
     }
 
-    z
+    violations.sort_by_key(|v| (v.bit, v.wire));
+    violations
 }
 
-fn parse_input(input: &str) -> (Vec<(Node, bool)>, Vec<Rule>) {
+fn to_dot(wires: &Wires, rules: &[Rule], suspects: &[Node]) -> String {
+    let slices = bit_slices(wires, rules);
+    let escaped = |node: Node| wires.name(node).replace('\\', "\\\\").replace('"', "\\\"");
+    let wire_line = |node: Node, label: String| {
+        let style = if suspects.contains(&node) {
+            ", style=filled, fillcolor=salmon, color=red"
+        } else {
+            ""
+        };
+        format!("    \"{}\" [label=\"{label}\"{style}];\n", escaped(node))
+    };
+
+    let mut out = String::from("digraph circuit {\n  rankdir=LR;\n  node [shape=box];\n");
+    let by_slice = slices.iter().into_group_map_by(|(_, &slice)| slice);
+    for slice in by_slice.keys().copied().sorted() {
+        out += &format!("  subgraph cluster_{slice} {{\n    label=\"bit {slice}\";\n");
+        for node in by_slice[&slice].iter().map(|(&node, _)| node).sorted() {
+            match rules.iter().find(|rule| rule.output == node) {
+                Some(rule) => {
+                    out += &wire_line(node, format!("{}\\n{}", escaped(node), rule.operation))
+                }
+                None => out += &wire_line(node, escaped(node)),
+            }
+        }
+        out += "  }\n";
+    }
+    for rule in rules {
+        for input in [rule.inputs.0, rule.inputs.1] {
+            out += &format!(
+                "  \"{}\" -> \"{}\";\n",
+                escaped(input),
+                escaped(rule.output)
+            );
+        }
+    }
+    out += "}\n";
+    out
+}
+
+fn to_verilog(wires: &Wires, rules: &[Rule]) -> String {
+    let width = Width::count(wires, rules);
+    let wire_name = |node: Node| {
+        if let Some(bit) = wires.x_bit(node) {
+            format!("x[{bit}]")
+        } else if let Some(bit) = wires.y_bit(node) {
+            format!("y[{bit}]")
+        } else if let Some(bit) = wires.z_bit(node) {
+            format!("z[{bit}]")
+        } else {
+            let name = wires.name(node).to_string();
+            let is_simple = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+            if is_simple {
+                name
+            } else {
+                // anything else needs to be an escaped identifier
+                format!("\\{name} ")
+            }
+        }
+    };
+
+    let mut out = format!(
+        "module circuit(\n  input [{}:0] x,\n  input [{}:0] y,\n  output [{}:0] z\n);\n",
+        width.x as i32 - 1,
+        width.y as i32 - 1,
+        width.z as i32 - 1,
+    );
+    for rule in rules
+        .iter()
+        .filter(|rule| wires.z_bit(rule.output).is_none())
+    {
+        out += &format!("  wire {};\n", wire_name(rule.output));
+    }
+    for (i, rule) in rules.iter().enumerate() {
+        let gate = match rule.operation {
+            Operation::And => "and",
+            Operation::Or => "or",
+            Operation::Xor => "xor",
+        };
+        out += &format!(
+            "  {gate} g{i} ({}, {}, {});\n",
+            wire_name(rule.output),
+            wire_name(rule.inputs.0),
+            wire_name(rule.inputs.1)
+        );
+    }
+    out += "endmodule\n";
+    out
+}
+
+fn parse_input(input: &str) -> (Wires, Vec<(Node, bool)>, Vec<Rule>) {
     let (part1, part2) = input.trim().split_once("\n\n").unwrap();
 
     let inputs = part1
         .lines()
         .map(|line| {
             let (node_name, val_str) = line.split_once(": ").unwrap();
-            let node = node_name.parse().unwrap();
-            let val = val_str == "1";
-            (node, val)
+            (node_name, val_str == "1")
         })
-        .collect();
+        .collect_vec();
 
     let rules = part2
         .lines()
         .map(|line| {
             let bits = line.split_whitespace().collect_vec();
-            let inputs = (bits[0].parse().unwrap(), bits[2].parse().unwrap());
-            let operation = bits[1].parse().unwrap();
-            let output = bits[4].parse().unwrap();
-            Rule {
-                inputs,
-                output,
-                operation,
-            }
+            ((bits[0], bits[2]), bits[1].parse().unwrap(), bits[4])
         })
+        .collect_vec();
+
+    let wires = Wires::new(
+        inputs
+            .iter()
+            .map(|&(name, _)| name)
+            .chain(rules.iter().flat_map(|&((a, b), _, out)| [a, b, out])),
+    );
+    let node = |name| wires.find(name).unwrap();
+
+    let inputs = inputs
+        .into_iter()
+        .map(|(name, val)| (node(name), val))
         .collect();
+    let rules = rules
+        .into_iter()
+        .map(|((a, b), operation, output)| Rule {
+            inputs: (node(a), node(b)),
+            output: node(output),
+            operation,
+        })
+        .collect();
+
+    (wires, inputs, rules)
+}
 
-    (inputs, rules)
+/// Number of x, y and z wires in a circuit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Width {
+    x: u16,
+    y: u16,
+    z: u16,
+}
+
+/// A circuit with more x, y or z wires than fit in a `u64`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TooWide(Width);
+
+impl Width {
+    /// Infer the width from the highest numbered x, y and z wire used, for a
+    /// circuit that can be evaluated on `u64`s
+    pub fn of(wires: &Wires, rules: &[Rule]) -> Result<Self, TooWide> {
+        let width = Self::count(wires, rules);
+        if width.x > 64 || width.y > 64 || width.z > 64 {
+            return Err(TooWide(width));
+        }
+        Ok(width)
+    }
+
+    /// Infer the width from the highest numbered x, y and z wire used,
+    /// however many there are
+    pub fn count(wires: &Wires, rules: &[Rule]) -> Self {
+        let mut width = Self { x: 0, y: 0, z: 0 };
+        for rule in rules {
+            for node in [rule.inputs.0, rule.inputs.1, rule.output] {
+                match wires.numbered(node) {
+                    Some(('x', bit)) => width.x = width.x.max(bit + 1),
+                    Some(('y', bit)) => width.y = width.y.max(bit + 1),
+                    Some((_, bit)) => width.z = width.z.max(bit + 1),
+                    None => (),
+                }
+            }
+        }
+        width
+    }
+
+    pub fn x_mask(self) -> u64 {
+        u64::MAX.checked_shr(64 - self.x as u32).unwrap_or(0)
+    }
+
+    pub fn y_mask(self) -> u64 {
+        u64::MAX.checked_shr(64 - self.y as u32).unwrap_or(0)
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -329,7 +949,7 @@ struct Rule {
     operation: Operation,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 enum Operation {
     And,
     Or,
@@ -337,15 +957,37 @@ enum Operation {
 }
 
 impl Operation {
-    pub fn apply(self, a: bool, b: bool) -> bool {
+    /// Apply the operation to 64 independent pairs of bits at once
+    pub fn apply_lanes(self, a: u64, b: u64) -> u64 {
         match self {
-            Operation::And => a && b,
-            Operation::Or => a || b,
+            Operation::And => a & b,
+            Operation::Or => a | b,
             Operation::Xor => a ^ b,
         }
     }
 }
 
+impl From<Operation> for BinOp {
+    fn from(op: Operation) -> Self {
+        match op {
+            Operation::And => BinOp::And,
+            Operation::Or => BinOp::Or,
+            Operation::Xor => BinOp::Xor,
+        }
+    }
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Operation::And => "AND",
+            Operation::Or => "OR",
+            Operation::Xor => "XOR",
+        };
+        Display::fmt(s, f)
+    }
+}
+
 impl FromStr for Operation {
     type Err = ();
 
@@ -359,85 +1001,108 @@ impl FromStr for Operation {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-struct Node(pub u16);
+/// A wire, as an index into the [Wires] of its circuit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Node(pub u32);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct InvalidNodeName;
+impl Node {
+    pub fn idx(self) -> usize {
+        self.0 as usize
+    }
+}
 
-impl FromStr for Node {
-    type Err = InvalidNodeName;
+/// The names of all wires in a circuit. They are kept sorted, so that nodes
+/// sort by name.
+#[derive(Debug, Clone)]
+struct Wires {
+    names: Vec<String>,
+    /// For names like `x07`: the prefix and the bit number
+    numbered: Vec<Option<(char, u16)>>,
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.len() != 3 {
-            return Err(InvalidNodeName);
-        }
-        let mut n = 0;
-        for c in s.bytes() {
-            let digit = match c {
-                c if c.is_ascii_lowercase() => (c - b'a') as u16 + 10,
-                c if c.is_ascii_digit() => (c - b'0') as u16,
-                _ => return Err(InvalidNodeName),
-            };
-            n *= 36;
-            n += digit;
+impl Wires {
+    /// Name every wire that appears, plus every x, y or z wire below the
+    /// highest one of its kind, so that [Wires::z] and friends always find
+    /// the bits of a circuit
+    pub fn new<'a>(names: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut names = names.into_iter().map(String::from).collect_vec();
+        for prefix in ['x', 'y', 'z'] {
+            let count = names
+                .iter()
+                .filter_map(|name| Self::parse_numbered(name))
+                .filter(|&(p, _)| p == prefix)
+                .map(|(_, bit)| bit + 1)
+                .max()
+                .unwrap_or(0);
+            names.extend((0..count).map(|bit| Self::name_for_bit(prefix, bit)));
         }
-        Ok(Self(n))
+        names.sort_unstable();
+        names.dedup();
+
+        let numbered = names
+            .iter()
+            .map(|name| Self::parse_numbered(name))
+            .collect();
+        Self { names, numbered }
     }
-}
 
-impl Display for Node {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let as_ascii_char = |digit| {
-            if digit < 10 {
-                b'0' + (digit as u8)
-            } else {
-                b'a' + (digit as u8 - 10)
-            }
-        };
-        let chars = [
-            as_ascii_char((self.0 / 1296) % 36),
-            as_ascii_char((self.0 / 36) % 36),
-            as_ascii_char(self.0 % 36),
-        ];
-        let s = str::from_utf8(&chars).unwrap();
-        Display::fmt(s, f)
+    /// The prefix and bit number of a name like `x07`. Only the canonical
+    /// spelling counts: `x07` and `x123` but not `x7`.
+    fn parse_numbered(name: &str) -> Option<(char, u16)> {
+        name.split_at_checked(1)
+            .filter(|(prefix, _)| matches!(*prefix, "x" | "y" | "z"))
+            .and_then(|(prefix, digits)| Some((prefix.chars().next()?, digits.parse().ok()?)))
+            .filter(|&(prefix, bit)| name == Self::name_for_bit(prefix, bit))
     }
-}
 
-impl Debug for Node {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "<<{}>>", self)
+    fn name_for_bit(prefix: char, idx: u16) -> String {
+        format!("{prefix}{idx:02}")
     }
-}
 
-impl Node {
-    pub const fn max() -> Self {
-        Self(36 * 36 * 36 - 1)
+    pub fn len(&self) -> usize {
+        self.names.len()
     }
 
-    pub fn x(idx: u16) -> Self {
-        Self(1296 * 33 + (idx / 10) * 36 + idx % 10)
+    pub fn find(&self, name: &str) -> Option<Node> {
+        let idx = self.names.binary_search_by(|n| n.as_str().cmp(name)).ok()?;
+        Some(Node(idx as u32))
     }
 
-    pub fn y(idx: u16) -> Self {
-        Self(1296 * 34 + (idx / 10) * 36 + idx % 10)
+    pub fn name(&self, node: Node) -> &str {
+        &self.names[node.idx()]
     }
 
-    pub fn z(idx: u16) -> Self {
-        Self(1296 * 35 + (idx / 10) * 36 + idx % 10)
+    pub fn z(&self, idx: u16) -> Option<Node> {
+        self.find(&Self::name_for_bit('z', idx))
     }
 
-    pub fn idx(self) -> usize {
-        self.0 as usize
+    pub fn is_x(&self, node: Node) -> bool {
+        self.x_bit(node).is_some()
     }
 
-    pub fn is_x(self) -> bool {
-        (Node::x(0).0..=Node::x(45).0).contains(&self.0)
+    pub fn x_bit(&self, node: Node) -> Option<u16> {
+        self.bit(node, 'x')
+    }
+
+    pub fn y_bit(&self, node: Node) -> Option<u16> {
+        self.bit(node, 'y')
+    }
+
+    pub fn z_bit(&self, node: Node) -> Option<u16> {
+        self.bit(node, 'z')
+    }
+
+    /// The bit number of a wire named like `x07`, if it has the given prefix
+    fn bit(&self, node: Node, prefix: char) -> Option<u16> {
+        match self.numbered(node) {
+            Some((p, bit)) if p == prefix => Some(bit),
+            _ => None,
+        }
     }
 
-    pub fn is_y(self) -> bool {
-        (Node::y(0).0..=Node::y(45).0).contains(&self.0)
+    /// For a wire named like `x07`, the prefix and the bit number
+    pub fn numbered(&self, node: Node) -> Option<(char, u16)> {
+        self.numbered[node.idx()]
     }
 }
 
@@ -512,4 +1177,172 @@ mod test {
         ";
         assert_eq!(day24part1(input2), 2024);
     }
+
+    /// 3-bit ripple-carry adder with z01 and z02 swapped
+    static SMALL_ADDER: &str = "\
+        x00: 0\n\
+        y00: 0\n\
+        \n\
+        x00 XOR y00 -> z00\n\
+        x00 AND y00 -> c00\n\
+        x01 XOR y01 -> s01\n\
+        x01 AND y01 -> a01\n\
+        s01 XOR c00 -> z02\n\
+        s01 AND c00 -> b01\n\
+        a01 OR b01 -> c01\n\
+        x02 XOR y02 -> s02\n\
+        x02 AND y02 -> a02\n\
+        s02 XOR c01 -> z01\n\
+        s02 AND c01 -> b02\n\
+        a02 OR b02 -> z03\n\
+    ";
+
+    #[test]
+    fn part2_small_adder() {
+        let (wires, _, rules) = parse_input(SMALL_ADDER);
+        assert_eq!(Width::of(&wires, &rules), Ok(Width { x: 3, y: 3, z: 4 }));
+        assert_eq!(day24part2(SMALL_ADDER), "z01,z02");
+
+        let (wires, _, rules) = parse_input("x00: 0\n\nx64 AND y00 -> z00\n");
+        assert_eq!(
+            Width::of(&wires, &rules),
+            Err(TooWide(Width { x: 65, y: 1, z: 1 }))
+        );
+        assert_eq!(
+            Circuit::new(&wires, &rules).unwrap_err(),
+            CircuitError::TooWide(Width { x: 65, y: 1, z: 1 })
+        );
+    }
+
+    #[test]
+    fn export_test() {
+        let dot = day24dot(SMALL_ADDER, &[]);
+        assert!(dot.contains("subgraph cluster_2 {"));
+        assert!(dot.contains("\"z01\" [label=\"z01\\nXOR\", style=filled"));
+        assert!(dot.contains("  \"s01\" -> \"z02\";\n"));
+
+        let verilog = day24verilog(SMALL_ADDER, &[]);
+        assert!(verilog.contains("  input [2:0] x,\n"));
+        assert!(verilog.contains("  output [3:0] z\n"));
+        assert!(verilog.contains("  xor g0 (z[0], x[0], y[0]);\n"));
+        assert!(verilog.contains("  wire c01;\n"));
+    }
+
+    #[test]
+    fn repair_test() {
+        // the example from the puzzle, where the circuit should compute x AND y
+        let input = "\
+            x00: 0\n\
+            y00: 0\n\
+            \n\
+            x00 AND y00 -> z05\n\
+            x01 AND y01 -> z02\n\
+            x02 AND y02 -> z01\n\
+            x03 AND y03 -> z03\n\
+            x04 AND y04 -> z04\n\
+            x05 AND y05 -> z00\n\
+        ";
+        let args = ["--target", "and", "--max-swaps", "1"];
+        assert_eq!(
+            day24repair(input, &args),
+            "no repair with at most 1 swaps\n"
+        );
+        let args = ["--target", "and", "--max-swaps", "2"];
+        assert_eq!(
+            day24repair(input, &args),
+            "swap z00 <-> z05\nswap z01 <-> z02\nz00,z01,z02,z05\n"
+        );
+
+        // as wide as fits in a u64, and then one more bit
+        let and_circuit = |bits| {
+            (0..bits).fold("x00: 0\ny00: 0\n\n".to_string(), |input, i| {
+                input + &format!("x{i:02} AND y{i:02} -> z{i:02}\n")
+            })
+        };
+        let args = ["--target", "and", "--max-swaps", "0"];
+        assert_eq!(day24repair(&and_circuit(64), &args), "\n");
+        assert_eq!(
+            day24repair(&and_circuit(65), &args),
+            "can't test 65 x, 65 y and 65 z bits: at most 64 of each fit\n"
+        );
+    }
+
+    #[test]
+    fn structure_test() {
+        let (wires, _, mut rules) = parse_input(SMALL_ADDER);
+        let node = |name| wires.find(name).unwrap();
+        let violations = check_structure(&wires, &rules);
+        assert_eq!(
+            violations
+                .iter()
+                .map(|v| (v.bit, wires.name(v.wire).to_string()))
+                .collect_vec(),
+            [(1, "z01".to_string()), (2, "z02".to_string())]
+        );
+        assert_eq!(
+            violations[0].to_string(),
+            "z01 = s02 XOR c01 (feeding nothing): expected one operand to be x01 XOR y01"
+        );
+
+        swap_rules(&mut rules, node("z01"), node("z02"));
+        assert!(check_structure(&wires, &rules).is_empty());
+    }
+
+    #[test]
+    fn proof_test() {
+        let (wires, _, mut rules) = parse_input(SMALL_ADDER);
+        let node = |name| wires.find(name).unwrap();
+        let circuit = Circuit::new(&wires, &rules).unwrap();
+        let Err(Counterexample { x, y, bit }) = prove_adder(&circuit) else {
+            panic!("broken adder passed");
+        };
+        assert_ne!(((x + y) >> bit) & 1, (circuit.evaluate(x, y) >> bit) & 1);
+
+        swap_rules(&mut rules, node("z01"), node("z02"));
+        assert_eq!(prove_adder(&Circuit::new(&wires, &rules).unwrap()), Ok(()));
+    }
+
+    #[test]
+    fn long_names_test() {
+        let input = SMALL_ADDER
+            .replace("c00", "carry_0")
+            .replace("c01", "carry_1")
+            .replace("a02", "2nd&a");
+        assert_eq!(day24part2(&input), "z01,z02");
+        let (wires, _, rules) = parse_input(&input);
+        assert_eq!(Wires::parse_numbered("z3"), None);
+        assert_eq!(Wires::parse_numbered("z03"), Some(('z', 3)));
+        assert!(to_verilog(&wires, &rules).contains("  or g11 (z[3], \\2nd&a , b02);\n"));
+        assert!(check_structure(&wires, &rules)[0]
+            .to_string()
+            .starts_with("z01 = s02 XOR carry_1 "));
+    }
+
+    #[test]
+    fn circuit_test() {
+        let (wires, _, mut rules) = parse_input(SMALL_ADDER);
+        let node = |name| wires.find(name).unwrap();
+        swap_rules(&mut rules, node("z01"), node("z02"));
+        let circuit = Circuit::new(&wires, &rules).unwrap();
+
+        let assignments = (0..8).cartesian_product(0..8).collect_vec();
+        let sums = circuit.evaluate_many(&assignments);
+        for (&(x, y), z) in assignments.iter().zip(sums) {
+            assert_eq!(x + y, z);
+            assert_eq!(circuit.evaluate(x, y), z);
+        }
+
+        // swapping a carry with a signal that depends on it makes a loop
+        swap_rules(&mut rules, node("c01"), node("b02"));
+        let Err(CircuitError::Cycle(stuck)) = Circuit::new(&wires, &rules) else {
+            panic!("expected a cycle");
+        };
+        assert!(stuck.contains(&"c01".to_string()));
+
+        rules.retain(|rule| rule.output != node("s01"));
+        assert_eq!(
+            Circuit::new(&wires, &rules).unwrap_err(),
+            CircuitError::Undriven("s01".to_string())
+        );
+    }
 }
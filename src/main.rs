@@ -1,5 +1,8 @@
 use std::{fmt::Display, fs::File, io::Read, path::Path, time::Instant};
 
+use itertools::Itertools;
+
+mod bdd;
 mod day01;
 mod day02;
 mod day03;
@@ -62,12 +65,48 @@ fn get_solution(day: i8) -> Option<(Box<dyn AdventPuzzleSolution>, &'static str)
     }
 }
 
+/// Extra modes for some days, beyond just solving the puzzle. A tool gets the
+/// puzzle input and any further command line arguments, and returns what to
+/// print.
+type Tool = fn(&str, &[&str]) -> String;
+
+/// A tool's arguments as `--option value` pairs
+fn option_pairs<'a, 'b>(args: &'b [&'a str]) -> impl Iterator<Item = (&'a str, &'a str)> + 'b {
+    if let [.., last] = args {
+        assert!(args.len().is_multiple_of(2), "missing value for {last}");
+    }
+    args.iter().copied().tuples()
+}
+
+fn get_tool(day: i8, name: &str) -> Option<Tool> {
+    match (day, name) {
+        (16, "costs") => Some(day16::day16costs),
+        (19, "arrangements") => Some(day19::day19arrangements),
+        (20, "report") => Some(day20::day20report),
+        (21, "chain") => Some(day21::day21chain),
+        (21, "sequences") => Some(day21::day21sequences),
+        (22, "orbits") => Some(day22::day22orbits),
+        (23, "cliques") => Some(day23::day23cliques),
+        (23, "count") => Some(day23::day23count),
+        (23, "report") => Some(day23::day23report),
+        (24, "check") => Some(day24::day24check),
+        (24, "dot") => Some(day24::day24dot),
+        (24, "repair") => Some(day24::day24repair),
+        (24, "verilog") => Some(day24::day24verilog),
+        _ => None,
+    }
+}
+
 fn main() {
     let args: Vec<_> = std::env::args().collect();
 
     if args.len() >= 2 {
         let day = args[1].parse().unwrap();
         let (solution, default_input) = get_solution(day).unwrap();
+        if let Some(tool) = args.get(2).and_then(|name| get_tool(day, name)) {
+            run_tool(tool, default_input, &args[3..]);
+            return;
+        }
         let input = args.get(2).map(|s| s.as_str()).unwrap_or(default_input);
         run_puzzle(&*solution, input);
     } else {
@@ -143,6 +182,28 @@ where
     }
 }
 
+/// Run a tool as `aoc2024 <day> <tool> [--input <path>] [args...]`
+fn run_tool(tool: Tool, default_input: &str, args: &[String]) {
+    let mut input = default_input;
+    let mut tool_args = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--input" {
+            input = args.next().expect("--input needs a path");
+        } else {
+            tool_args.push(arg.as_str());
+        }
+    }
+
+    let mut s = String::new();
+    if input == "-" {
+        std::io::stdin().read_to_string(&mut s).unwrap();
+    } else {
+        File::open(input).unwrap().read_to_string(&mut s).unwrap();
+    }
+    print!("{}", tool(&s, &tool_args));
+}
+
 fn run_puzzle(solution: &dyn AdventPuzzleSolution, input: &str) {
     let t0 = Instant::now();
     let answer = if input == "-" {